@@ -0,0 +1,16 @@
+#!/bin/bash
+#
+# Benchmarks against stored baselines (kept by Criterion in target/criterion)
+#   ./bench.sh save main       # records the baseline "main" (e.g. on the main branch)
+#   ./bench.sh compare main    # compares the current code with it (reports regressions)
+# PROCURE2PAY_BENCH_EVENTS=10000,1000000 limits the log sizes (default: 10k, 1M, 10M events)
+set -e
+
+mode=${1:?Usage: $0 save|compare BASELINE}
+baseline=${2:?Usage: $0 save|compare BASELINE}
+
+case "$mode" in
+    save) cargo bench --bench engines -- --save-baseline "$baseline" ;;
+    compare) cargo bench --bench engines -- --baseline "$baseline" ;;
+    *) echo "Unknown mode: $mode (expected save or compare)"; exit 1 ;;
+esac
@@ -0,0 +1,98 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  engines: Criterion benchmarks of parsing, sorting and variant counting
+//          * synthetic logs of 10k, 1M and 10M events (deterministic, from a fixed seed)
+//          * sizes can be overridden with PROCURE2PAY_BENCH_EVENTS=10000,1000000
+//          * compare against a stored baseline with `./bench.sh save|compare NAME`
+//
+use chrono::NaiveDateTime;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use procure2pay::csv_parser::{self, Event};
+use procure2pay::generator::{self, Distribution, Model, Noise};
+use procure2pay::{interned, parallel, sequential};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+
+type Engine = fn(Vec<Event>) -> Result<Vec<(Vec<u8>, usize)>, Box<dyn std::error::Error>>;
+
+const DEFAULT_SIZES: &str = "10000,1000000,10000000";
+const SEED: u64 = 42;
+
+fn sizes() -> Vec<usize> {
+    std::env::var("PROCURE2PAY_BENCH_EVENTS")
+        .unwrap_or_else(|_| DEFAULT_SIZES.to_string())
+        .split(',')
+        .map(|size| size.trim().parse().expect("PROCURE2PAY_BENCH_EVENTS must be a list of numbers"))
+        .collect()
+}
+
+// A few typical Procure to Pay variants, with duplicates and swaps as in real logs
+fn log(events: usize) -> Vec<Event> {
+    let variant = |names: &[&str], weight: f64| (names.iter().map(|name| name.to_string()).collect(), weight);
+    let model = Model::Variants(vec![
+        variant(&["Create FI invoice by vendor", "Post invoice in FI", "Clear open item"], 40.0),
+        variant(&["Create purchase order item", "Change purchase order item", "Record order confirmation", "Create MM invoice by vendor",
+                  "Enter goods receipt", "Post invoice in MM", "Clear open item"], 20.0),
+        variant(&["Create MM invoice by vendor", "Create purchase order item", "Enter goods receipt", "Post invoice in MM", "Clear open item"], 15.0),
+        variant(&["Create purchase order item", "Create MM invoice by vendor", "Post invoice in MM", "Set payment block", "Enter goods receipt",
+                  "Remove payment block", "Clear open item"], 10.0),
+        variant(&["Create MM invoice by vendor", "Post invoice in MM", "Clear open item"], 8.0),
+    ]);
+    let config = generator::Config {
+        cases: events / 4 + 1,
+        seed: SEED,
+        start: NaiveDateTime::parse_from_str("2014-01-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        arrival: Distribution::Exponential(600.0),
+        durations: HashMap::new(),
+        default_duration: Distribution::Exponential(86400.0),
+        noise: Noise { swap: 0.01, missing: 0.01, duplicate: 0.05 },
+        shuffle: true,
+    };
+    let mut log = generator::generate(&model, &config).unwrap();
+    log.truncate(events);
+    log
+}
+
+fn benchmarks(c: &mut Criterion) {
+    for size in sizes() {
+        let events = log(size);
+        let csv_path = std::env::temp_dir().join(format!("procure2pay_bench_{}.csv", size));
+        generator::write_csv(&events, &mut BufWriter::new(File::create(&csv_path).unwrap())).unwrap();
+
+        let mut group = c.benchmark_group("procure2pay");
+        group.throughput(Throughput::Elements(size as u64));
+        if size >= 1_000_000 {
+            group.sample_size(10);
+        }
+
+        group.bench_with_input(BenchmarkId::new("parse", size), &csv_path, |b, path| {
+            b.iter(|| csv_parser::parse_csv(path.to_str().unwrap()).unwrap())
+        });
+        // The sort of the parallel engine (by case, timestamp and activity)
+        group.bench_with_input(BenchmarkId::new("sort", size), &events, |b, events| {
+            b.iter_batched(
+                || events.clone(),
+                |mut events| events.par_sort_unstable_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)).then_with(|| a.2.cmp(&b.2))),
+                BatchSize::LargeInput,
+            )
+        });
+        let engines: [(&str, Engine); 3] = [
+            ("sequential", sequential::process_cases),
+            ("parallel", parallel::process_cases),
+            ("interned", interned::process_cases),
+        ];
+        for (name, engine) in engines {
+            group.bench_with_input(BenchmarkId::new(name, size), &events, |b, events| {
+                b.iter_batched(|| events.clone(), engine, BatchSize::LargeInput)
+            });
+        }
+        group.finish();
+        let _ = std::fs::remove_file(&csv_path);
+    }
+}
+
+criterion_group!(benches, benchmarks);
+criterion_main!(benches);
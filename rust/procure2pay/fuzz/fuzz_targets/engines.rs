@@ -0,0 +1,36 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  engines: Arbitrary bytes through the parser and all engines, which must agree with gold
+//          * unknown activities are errors (of all engines alike), never panics
+//
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use procure2pay::{csv_parser, interned, parallel, sequential};
+
+// Variant counts in a canonical order (variants with the same count come in any order)
+fn canonical(mut variants: Vec<(Vec<u8>, usize)>) -> Vec<(Vec<u8>, usize)> {
+    variants.sort();
+    variants
+}
+
+fuzz_target!(|data: &[u8]| {
+    let events = match csv_parser::parse_csv_from(data) {
+        Ok(events) => events,
+        Err(_) => return,
+    };
+
+    let gold = match sequential::process_cases(events.clone()) {
+        Ok(variants) => canonical(variants),
+        Err(_) => {
+            assert!(parallel::process_cases(events.clone()).is_err());
+            assert!(interned::process_cases(events).is_err());
+            return;
+        }
+    };
+    assert_eq!(canonical(parallel::process_cases(events.clone()).unwrap()), gold);
+    assert_eq!(canonical(interned::process_cases(events.clone()).unwrap()), gold);
+    let cases: usize = gold.iter().map(|(_, count)| count).sum();
+    assert!(cases <= events.len());
+});
@@ -0,0 +1,16 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  parse_csv: Arbitrary bytes through the CSV parser (errors are fine, panics are not)
+//
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use procure2pay::csv_parser;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(events) = csv_parser::parse_csv_from(data) {
+        // Never more events than lines
+        assert!(events.len() <= data.split(|&byte| byte == b'\n').count());
+    }
+});
@@ -0,0 +1,25 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  stream_line: Arbitrary lines through the stream parser and monitor (both formats)
+//
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use procure2pay::activities;
+use procure2pay::incremental::State;
+use procure2pay::stream::{Config, Format, Monitor};
+use procure2pay::timezones::Zone;
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    for format in [Format::Csv, Format::Json] {
+        let config = Config { format, top: 10, with_names: true, case_timeout: Some(chrono::Duration::hours(1)), zone: Zone::utc(), display_zone: None };
+        let mut monitor = Monitor::new(State::new(activities::str_to_num("Clear open item")), config);
+        for line in text.lines() {
+            monitor.process_line(line);
+        }
+        monitor.expire();
+        let _ = monitor.snapshot();
+    }
+});
@@ -0,0 +1,32 @@
+#!/bin/bash
+#
+# Derives the seed corpus of all fuzz targets from the sample log:
+# * small logs with the header and the events of a few cases (whole cases, so the engines
+#   see complete variants)
+# * a few lines of the same log as newline-delimited JSON for stream_line
+#   ./seed_corpus.sh [../sample_log.csv]
+set -e
+
+log=${1:-../sample_log.csv}
+cases_per_seed=3
+seeds=64
+
+mkdir -p corpus/parse_csv corpus/engines corpus/stream_line
+header=$(head -n 1 "$log")
+tail -n +2 "$log" | cut -d ';' -f 1 | awk '!seen[$0]++' | head -n $((cases_per_seed * seeds)) > /tmp/procure2pay_seed_cases
+
+split -l $cases_per_seed /tmp/procure2pay_seed_cases /tmp/procure2pay_seed_
+for cases in /tmp/procure2pay_seed_??; do
+    seed=$(basename "$cases")
+    seed=${seed#procure2pay_}
+    {
+        echo "$header"
+        awk -F ';' 'NR == FNR { wanted[$1] = 1; next } FNR > 1 && ($1 in wanted)' "$cases" "$log"
+    } > "corpus/engines/$seed.csv"
+    cp "corpus/engines/$seed.csv" "corpus/parse_csv/$seed.csv"
+    awk -F ';' 'NR > 1 { printf "{\"case_id\": \"%s\", \"activity\": \"%s\", \"timestamp\": \"%s\"}\n", $1, $2, $3 }' \
+        "corpus/engines/$seed.csv" > "corpus/stream_line/$seed.json"
+    rm "$cases"
+done
+rm /tmp/procure2pay_seed_cases
+echo "Seeds: $(ls corpus/engines | wc -l) per target"
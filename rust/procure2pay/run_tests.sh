@@ -9,6 +9,7 @@ cargo run || echo "We expect non-zero exit code (no args)"
 # Run Unit tests for both variants
 cargo test --features sequential
 cargo test --features parallel
+cargo test --features interned
 
 # Run with samples
 cargo  run --release sample_log.csv --with-names
@@ -41,4 +42,16 @@ if ! diff parallel.txt sequential.txt; then
     exit 1;
 fi
 
+# The same for the interned engine
+for decimate in 0 10; do
+    cargo  run --release sample_log.csv --decimate $decimate --interned --no-time-eval > interned.txt
+    cargo  run --release sample_log.csv --decimate $decimate --gold --no-time-eval > sequential.txt
+    if ! diff interned.txt sequential.txt; then
+        echo "Output of the interned and golden variants differ!";
+        cat interned.txt;
+        cat sequential.txt;
+        exit 1;
+    fi
+done
+
 echo "All checks passed!"
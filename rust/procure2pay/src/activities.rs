@@ -3,48 +3,64 @@
 //
 //  activities: Utility to convert activity name to enum/number.
 //  warning: This is module is generated, use `procure2pay ... --crunch-activities` to regenerate
+//           (except for the activities registered at runtime, numbered from FIRST_REGISTERED)
 //
 use chrono::NaiveDateTime;
 use std::collections::HashMap;
+use std::sync::RwLock;
+
+// Activities unknown to the generated tables (e.g. categories of a label mapping)
+pub const FIRST_REGISTERED: u8 = 128;
+static REGISTERED: RwLock<Vec<&'static str>> = RwLock::new(Vec::new());
 
 pub fn crunch_activities(cases: Vec<(String, NaiveDateTime, String)>) {
     let activities = preprocess_activities(cases);
     generate_function_prototypes(activities.0, activities.1);
 }
 
+// Number of a known activity (panics otherwise, see check() for the activities of a log)
 pub fn str_to_num(activity: &str) -> u8 {
+    try_str_to_num(activity).unwrap_or_else(|| panic!("Unknown activity: {}", activity))
+}
+
+// Number of the activity, an error if it's unknown (e.g. a typo in the log)
+pub fn check(activity: &str) -> Result<u8, Box<dyn std::error::Error>> {
+    try_str_to_num(activity).ok_or_else(|| format!("Unknown activity: {}", activity).into())
+}
+
+pub fn try_str_to_num(activity: &str) -> Option<u8> {
     match activity {
-        "Reduce purchase order item quantity" => 10,
-        "Change cash discount days 2" => 24,
-        "Change purchase order (currency)" => 27,
-        "Unblock purchase order item" => 22,
-        "Change terms of payment key" => 20,
-        "Change item text" => 25,
-        "Reduce purchase order item price" => 13,
-        "Increase purchase order item quantity" => 11,
-        "Create purchase order item" => 0,
-        "Create MM invoice by vendor" => 1,
-        "Change purchase order item" => 3,
-        "Change payment method" => 18,
-        "Increase purchase order item net value" => 16,
-        "Change cash discount percentage 1" => 21,
-        "Change purchase order (other)" => 23,
-        "Create FI invoice by vendor" => 9,
-        "Enter goods receipt" => 6,
-        "Post invoice in MM" => 8,
-        "Set final delivery indicator" => 17,
-        "Set payment block" => 26,
-        "Change baseline date for payment" => 29,
-        "Post invoice in FI" => 7,
-        "Record order confirmation" => 12,
-        "Change purchase order (purchasing group)" => 19,
-        "Increase purchase order item price" => 14,
-        "Block purchase order item" => 28,
-        "Remove payment block" => 5,
-        "Clear open item" => 4,
-        "Change cash discount days 1" => 15,
-        "Reduce purchase order item net value" => 2,
-        _ => panic!("Unknown activity"),
+        "Reduce purchase order item quantity" => Some(10),
+        "Change cash discount days 2" => Some(24),
+        "Change purchase order (currency)" => Some(27),
+        "Unblock purchase order item" => Some(22),
+        "Change terms of payment key" => Some(20),
+        "Change item text" => Some(25),
+        "Reduce purchase order item price" => Some(13),
+        "Increase purchase order item quantity" => Some(11),
+        "Create purchase order item" => Some(0),
+        "Create MM invoice by vendor" => Some(1),
+        "Change purchase order item" => Some(3),
+        "Change payment method" => Some(18),
+        "Increase purchase order item net value" => Some(16),
+        "Change cash discount percentage 1" => Some(21),
+        "Change purchase order (other)" => Some(23),
+        "Create FI invoice by vendor" => Some(9),
+        "Enter goods receipt" => Some(6),
+        "Post invoice in MM" => Some(8),
+        "Set final delivery indicator" => Some(17),
+        "Set payment block" => Some(26),
+        "Change baseline date for payment" => Some(29),
+        "Post invoice in FI" => Some(7),
+        "Record order confirmation" => Some(12),
+        "Change purchase order (purchasing group)" => Some(19),
+        "Increase purchase order item price" => Some(14),
+        "Block purchase order item" => Some(28),
+        "Remove payment block" => Some(5),
+        "Clear open item" => Some(4),
+        "Change cash discount days 1" => Some(15),
+        "Reduce purchase order item net value" => Some(2),
+        _ => registered_num(activity),
     }
 }
 
@@ -80,10 +96,51 @@ pub fn num_to_str(num: u8) -> &'static str {
         17 => "Set final delivery indicator",
         23 => "Change purchase order (other)",
         27 => "Change purchase order (currency)",
-        _ => panic!("Unknown activity number"),
+        _ => registered_name(num),
     }
 }
 
+// Number of the activity, registering it if it's unknown (the name is kept for the rest of the run)
+pub fn register(activity: &str) -> Result<u8, Box<dyn std::error::Error>> {
+    if let Some(num) = try_str_to_num(activity) {
+        return Ok(num);
+    }
+    let mut registered = REGISTERED.write().unwrap_or_else(|err| err.into_inner());
+    if let Some(i) = registered.iter().position(|name| *name == activity) {
+        return Ok(FIRST_REGISTERED + i as u8);
+    }
+    if registered.len() > (u8::MAX - FIRST_REGISTERED) as usize {
+        return Err(format!("Too many activities, can't add: {}", activity).into());
+    }
+    registered.push(Box::leak(activity.to_string().into_boxed_str()));
+    Ok(FIRST_REGISTERED + (registered.len() - 1) as u8)
+}
+
+fn registered_num(activity: &str) -> Option<u8> {
+    let registered = REGISTERED.read().unwrap_or_else(|err| err.into_inner());
+    registered.iter().position(|name| *name == activity).map(|i| FIRST_REGISTERED + i as u8)
+}
+
+fn registered_name(num: u8) -> &'static str {
+    let registered = REGISTERED.read().unwrap_or_else(|err| err.into_inner());
+    num.checked_sub(FIRST_REGISTERED)
+        .and_then(|i| registered.get(i as usize).copied())
+        .unwrap_or_else(|| panic!("Unknown activity number"))
+}
+
+// Replaces the activity numbers of the variants with their names
+pub fn convert_variants_to_strings(variants: Vec<&(Vec<u8>, usize)>) -> Vec<(Vec<String>, usize)> {
+    variants
+        .into_iter()
+        .map(|(activity_numbers, count)| {
+            let activity_names = activity_numbers
+                .iter()
+                .map(|&num| num_to_str(num).to_string()) // Convert &str to String
+                .collect::<Vec<String>>();
+            (activity_names, *count)
+        })
+        .collect()
+}
 
 fn preprocess_activities(cases: Vec<(String, NaiveDateTime, String)>) -> (HashMap<String, u8>, HashMap<u8, String>) {
     let mut activity_map: HashMap<String, u8> = HashMap::new();
@@ -102,14 +159,14 @@ fn preprocess_activities(cases: Vec<(String, NaiveDateTime, String)>) -> (HashMa
 }
 
 fn generate_function_prototypes(activity_map: HashMap<String, u8>, reverse_map: HashMap<u8, String>) {
-    println!("fn str_to_num(activity: &str) -> u8 {{");
+    println!("fn try_str_to_num(activity: &str) -> Option<u8> {{");
     println!("    match activity {{");
 
     for (activity, num) in &activity_map {
-        println!("        \"{}\" => {},", activity, num);
+        println!("        \"{}\" => Some({}),", activity, num);
     }
 
-    println!("        _ => panic!(\"Unknown activity\"),");
+    println!("        _ => registered_num(activity),");
     println!("    }}");
     println!("}}");
 
@@ -120,7 +177,7 @@ fn generate_function_prototypes(activity_map: HashMap<String, u8>, reverse_map:
         println!("        {} => \"{}\",", num, activity);
     }
 
-    println!("        _ => panic!(\"Unknown activity number\"),");
+    println!("        _ => registered_name(num),");
     println!("    }}");
     println!("}}");
 }
@@ -0,0 +1,188 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  anomalies: Scores how unusual each case is (for auditors: the suspicious cases first)
+//          * rare variant, rarest directly-follows transition and repeated activities score by
+//            their surprisal relative to the log size: 1 for a single case, 0 for all cases
+//          * extreme throughput time (too long or too short) by its robust z-score (median and
+//            median absolute deviation), from 0 at z = 3 up to 1 at z = 10
+//          * the score of a case is the mean of the four, each notable one is explained
+//          * the top-K cases with their explanations and traces as JSON
+//
+use crate::activities;
+use crate::timezones::{self, Zone};
+use crate::traces::Trace;
+
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+
+// Robust z-scores from here on are extreme (and up to MAX_Z they score more)
+const MIN_Z: f64 = 3.0;
+const MAX_Z: f64 = 10.0;
+// Components from here on are explained (e.g. a variant of at most 1/sqrt(cases) of the cases)
+const NOTABLE: f64 = 0.5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly<'a> {
+    pub trace: &'a Trace,
+    // mean of the components below, all between 0 and 1
+    pub score: f64,
+    pub variant: f64,
+    pub transitions: f64,
+    pub duration: f64,
+    pub activity_counts: f64,
+    pub explanations: Vec<String>,
+}
+
+// Surprisal of something seen in `count` of the cases, relative to that of a single case
+fn rarity(count: usize, cases: usize) -> f64 {
+    if cases <= 1 || count == 0 {
+        return 0.0;
+    }
+    (-(count as f64 / cases as f64).log2() / (cases as f64).log2()).clamp(0.0, 1.0)
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    match values.len() {
+        0 => 0.0,
+        n if n % 2 == 0 => (values[n / 2 - 1] + values[n / 2]) / 2.0,
+        n => values[n / 2],
+    }
+}
+
+// Distinct directly-follows transitions of a trace
+fn transitions(trace: &Trace) -> HashSet<(u8, u8)> {
+    trace.activities.windows(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+// Number of times each activity is in the trace (only consecutive repetitions are merged)
+fn activity_counts(trace: &Trace) -> HashMap<u8, usize> {
+    let mut counts = HashMap::new();
+    for &activity in &trace.activities {
+        *counts.entry(activity).or_insert(0) += 1;
+    }
+    counts
+}
+
+fn cases_text(count: usize, cases: usize) -> String {
+    format!("{} of {} cases", count, cases)
+}
+
+pub fn score(traces: &[Trace]) -> Vec<Anomaly<'_>> {
+    let cases = traces.len();
+    let mut variant_cases: HashMap<&Vec<u8>, usize> = HashMap::new();
+    let mut transition_cases: HashMap<(u8, u8), usize> = HashMap::new();
+    // (activity, number of times) -> cases with the activity at least that many times
+    let mut repetition_cases: HashMap<(u8, usize), usize> = HashMap::new();
+    for trace in traces {
+        *variant_cases.entry(&trace.activities).or_insert(0) += 1;
+        for transition in transitions(trace) {
+            *transition_cases.entry(transition).or_insert(0) += 1;
+        }
+        for (activity, count) in activity_counts(trace) {
+            for times in 2..=count {
+                *repetition_cases.entry((activity, times)).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let days: Vec<f64> = traces.iter().map(Trace::throughput_days).collect();
+    let median_days = median(&mut days.clone());
+    let deviation = median(&mut days.iter().map(|d| (d - median_days).abs()).collect::<Vec<_>>());
+    // Scaled to the standard deviation of a normal distribution (mean deviation if most are equal)
+    let scale = match deviation {
+        deviation if deviation > 0.0 => 1.4826 * deviation,
+        _ => 1.2533 * days.iter().map(|d| (d - median_days).abs()).sum::<f64>() / cases.max(1) as f64,
+    };
+
+    let mut anomalies: Vec<Anomaly> = traces
+        .iter()
+        .zip(days)
+        .map(|(trace, days)| {
+            let mut explanations = Vec::new();
+            let variant_count = variant_cases[&trace.activities];
+            let variant = rarity(variant_count, cases);
+            if variant >= NOTABLE {
+                explanations.push(format!("Rare variant ({})", cases_text(variant_count, cases)));
+            }
+
+            let rarest_transition = transitions(trace).into_iter().map(|transition| (transition_cases[&transition], transition)).min();
+            let transitions = rarest_transition.map_or(0.0, |(count, _)| rarity(count, cases));
+            if let (true, Some((count, (from, to)))) = (transitions >= NOTABLE, rarest_transition) {
+                explanations.push(format!(
+                    "Rare transition {} -> {} ({})",
+                    activities::num_to_str(from),
+                    activities::num_to_str(to),
+                    cases_text(count, cases)
+                ));
+            }
+
+            let z = match scale {
+                scale if scale > 0.0 => (days - median_days).abs() / scale,
+                _ => 0.0,
+            };
+            let duration = ((z - MIN_Z) / (MAX_Z - MIN_Z)).clamp(0.0, 1.0);
+            if z > MIN_Z {
+                let direction = if days > median_days { "long" } else { "short" };
+                explanations.push(format!("Extremely {} throughput time: {:.1} days (median {:.1} days, z = {:.1})", direction, days, median_days, z));
+            }
+
+            let rarest_repetition = activity_counts(trace)
+                .into_iter()
+                .filter(|(_, count)| *count >= 2)
+                .map(|(activity, count)| (repetition_cases[&(activity, count)], activity, count))
+                .min();
+            let activity_counts = rarest_repetition.map_or(0.0, |(cases_with, _, _)| rarity(cases_with, cases));
+            if let (true, Some((cases_with, activity, count))) = (activity_counts >= NOTABLE, rarest_repetition) {
+                explanations.push(format!(
+                    "{} {} times ({} with it at least {} times)",
+                    activities::num_to_str(activity),
+                    count,
+                    cases_text(cases_with, cases),
+                    count
+                ));
+            }
+
+            Anomaly {
+                trace,
+                score: (variant + transitions + duration + activity_counts) / 4.0,
+                variant,
+                transitions,
+                duration,
+                activity_counts,
+                explanations,
+            }
+        })
+        .collect();
+    anomalies.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.trace.case_id.cmp(&b.trace.case_id)));
+    anomalies
+}
+
+// The top-K anomalies with their traces (times in UTC, or in the zone)
+pub fn to_json(anomalies: &[Anomaly], top: usize, zone: Option<&Zone>) -> Value {
+    json!(anomalies
+        .iter()
+        .take(top)
+        .map(|anomaly| json!({
+            "case_id": anomaly.trace.case_id,
+            "score": anomaly.score,
+            "components": {
+                "variant": anomaly.variant,
+                "transitions": anomaly.transitions,
+                "duration": anomaly.duration,
+                "activity_counts": anomaly.activity_counts,
+            },
+            "explanations": anomaly.explanations,
+            "throughput_days": anomaly.trace.throughput_days(),
+            "trace": anomaly.trace.activities
+                .iter()
+                .zip(&anomaly.trace.timestamps)
+                .map(|(&activity, &timestamp)| json!({
+                    "activity": activities::num_to_str(activity),
+                    "timestamp": timezones::display(timestamp, zone),
+                }))
+                .collect::<Vec<_>>(),
+        }))
+        .collect::<Vec<_>>())
+}
@@ -0,0 +1,63 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  assignments: Case-to-variant assignments (to drill from a variant into the documents)
+//          * variant_id is the 1-based rank of the variant (the same as in the printed top list)
+//          * written as CSV (case_id;variant_id) or JSON (variants with all their case ids),
+//            chosen by the file extension
+//
+use crate::activities;
+
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fs;
+use std::io::{BufWriter, Write};
+
+// Variants with their case ids, as returned by process_cases_with_case_ids()
+pub type VariantCases = Vec<(Vec<u8>, Vec<String>)>;
+
+// The usual (variant, count) list
+pub fn counts(variants: &VariantCases) -> Vec<(Vec<u8>, usize)> {
+    variants.iter().map(|(variant, case_ids)| (variant.clone(), case_ids.len())).collect()
+}
+
+// (case_id, variant_id), ordered by case_id
+pub fn assign(variants: &VariantCases) -> Vec<(String, usize)> {
+    let mut assignments: Vec<(String, usize)> = variants
+        .iter()
+        .enumerate()
+        .flat_map(|(i, (_, case_ids))| case_ids.iter().map(move |case_id| (case_id.clone(), i + 1)))
+        .collect();
+    assignments.sort();
+    assignments
+}
+
+pub fn to_json(variants: &VariantCases) -> Value {
+    json!(variants
+        .iter()
+        .enumerate()
+        .map(|(i, (variant, case_ids))| json!({
+            "variant_id": i + 1,
+            "count": case_ids.len(),
+            "activities": variant.iter().map(|&num| activities::num_to_str(num)).collect::<Vec<_>>(),
+            "cases": case_ids,
+        }))
+        .collect::<Vec<_>>())
+}
+
+pub fn write(file_path: &str, variants: &VariantCases) -> Result<(), Box<dyn Error>> {
+    if file_path.ends_with(".json") {
+        fs::write(file_path, to_json(variants).to_string())?;
+        return Ok(());
+    }
+    if !file_path.ends_with(".csv") {
+        return Err(format!("Unsupported assignments file (expected .csv or .json): {}", file_path).into());
+    }
+    let mut writer = BufWriter::new(fs::File::create(file_path)?);
+    writeln!(writer, "case_id;variant_id")?;
+    for (case_id, variant_id) in assign(variants) {
+        writeln!(writer, "{};{}", case_id, variant_id)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,223 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  breakdown: Variants broken down by a case attribute (vendor, company code, order value band, ...)
+//          * case attributes come from the extra columns of the log (the earliest non-empty value
+//            of each column in a case) and/or a case table joined on the case id
+//          * numeric attributes can be grouped into value bands (e.g. 1000,10000 -> <1000,
+//            1000-10000, >=10000)
+//          * top-N variants per group and of all cases, with the divergence of each group from
+//            all cases (Kullback-Leibler, bits) and its weighted mean over the groups (the
+//            Jensen-Shannon divergence of the groups, i.e. how much the group tells about the variant)
+//
+use crate::activities;
+use crate::csv_parser::{Attributes, Event};
+
+use chrono::NaiveDateTime;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+// case_id -> attribute name -> value
+pub type CaseAttributes = HashMap<String, Attributes>;
+
+// Label of the cases without the attribute
+pub const MISSING: &str = "(missing)";
+
+pub struct Group {
+    pub value: String,
+    pub cases: usize,
+    // sorted by count (then by variant)
+    pub variants: Vec<(Vec<u8>, usize)>,
+    // from the variant distribution of all cases, in bits
+    pub divergence: f64,
+}
+
+pub struct Breakdown {
+    pub attribute: String,
+    pub cases: usize,
+    pub variants: Vec<(Vec<u8>, usize)>,
+    // sorted by the number of cases
+    pub groups: Vec<Group>,
+    pub divergence: f64,
+    // the divergence relative to the entropy of the variants (0: same behavior, 1: the group
+    // determines the variant)
+    pub normalized_divergence: f64,
+}
+
+// Case attributes from the extra columns of the events (the earliest non-empty value in a case)
+pub fn from_events(events: &[(Event, Attributes)]) -> CaseAttributes {
+    let mut earliest: HashMap<&str, HashMap<&str, (NaiveDateTime, &str)>> = HashMap::new();
+    for ((case_id, timestamp, _), attributes) in events {
+        let case = earliest.entry(case_id).or_default();
+        for (name, value) in attributes.iter().filter(|(_, value)| !value.trim().is_empty()) {
+            let entry = case.entry(name).or_insert((*timestamp, value));
+            if *timestamp < entry.0 {
+                *entry = (*timestamp, value);
+            }
+        }
+    }
+    earliest
+        .into_iter()
+        .map(|(case_id, values)| {
+            (case_id.to_string(), values.into_iter().map(|(name, (_, value))| (name.to_string(), value.trim().to_string())).collect())
+        })
+        .collect()
+}
+
+// Reads a case table: CSV (';' separated) with a header, the case id in the first column
+pub fn read_case_table(file_path: &str) -> Result<CaseAttributes, Box<dyn Error>> {
+    let mut lines = BufReader::new(File::open(file_path)?).lines();
+    let header: Vec<String> = match lines.next() {
+        Some(header) => header?.split(';').map(|name| name.trim().to_string()).collect(),
+        None => return Ok(CaseAttributes::new()),
+    };
+    let mut table = CaseAttributes::new();
+    for line in lines {
+        let line = line?;
+        let fields: Vec<&str> = line.split(';').collect();
+        if fields.len() != header.len() {
+            continue; // Skip malformed lines
+        }
+        let attributes = header[1..]
+            .iter()
+            .zip(&fields[1..])
+            .filter(|(_, value)| !value.trim().is_empty())
+            .map(|(name, value)| (name.clone(), value.trim().to_string()))
+            .collect();
+        table.insert(fields[0].to_string(), attributes);
+    }
+    Ok(table)
+}
+
+// Adds the attributes of the case table (they override those of the events)
+pub fn join(case_attributes: &mut CaseAttributes, table: CaseAttributes) {
+    for (case_id, attributes) in table {
+        case_attributes.entry(case_id).or_default().extend(attributes);
+    }
+}
+
+// Parses the upper bounds of the value bands, e.g. "1000,10000,100000"
+pub fn parse_bands(bands: &str) -> Result<Vec<f64>, Box<dyn Error>> {
+    let bounds = bands
+        .split(',')
+        .map(|bound| bound.trim().parse::<f64>().map_err(|_| format!("Invalid band bound: {}", bound)))
+        .collect::<Result<Vec<f64>, _>>()?;
+    if bounds.windows(2).any(|pair| pair[0] >= pair[1]) || bounds.iter().any(|bound| !bound.is_finite()) {
+        return Err(format!("Band bounds must be ascending numbers: {}", bands).into());
+    }
+    Ok(bounds)
+}
+
+// The group of an attribute value: the value itself, or its band
+fn group_of(value: Option<&String>, bands: &[f64]) -> String {
+    let value = match value {
+        Some(value) => value,
+        None => return MISSING.to_string(),
+    };
+    if bands.is_empty() {
+        return value.clone();
+    }
+    let number = match value.parse::<f64>() {
+        Ok(number) if number.is_finite() => number,
+        _ => return format!("{} (not a number)", value),
+    };
+    match bands.iter().position(|&bound| number < bound) {
+        Some(0) => format!("<{}", bands[0]),
+        Some(i) => format!("{}-{}", bands[i - 1], bands[i]),
+        None => format!(">={}", bands[bands.len() - 1]),
+    }
+}
+
+// Sorted by count, ties by variant (so that the order doesn't depend on the engine)
+fn sorted(counts: HashMap<&Vec<u8>, usize>) -> Vec<(Vec<u8>, usize)> {
+    let mut variants: Vec<(Vec<u8>, usize)> = counts.into_iter().map(|(variant, count)| (variant.clone(), count)).collect();
+    variants.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    variants
+}
+
+fn entropy(variants: &[(Vec<u8>, usize)], cases: usize) -> f64 {
+    variants
+        .iter()
+        .map(|(_, count)| *count as f64 / cases as f64)
+        .map(|p| -p * p.log2())
+        .sum()
+}
+
+impl Breakdown {
+    // Breaks down the variants with their case ids (as returned by process_cases_with_case_ids)
+    pub fn compute(variant_cases: &[(Vec<u8>, Vec<String>)], case_attributes: &CaseAttributes, attribute: &str, bands: &[f64]) -> Breakdown {
+        let mut overall: HashMap<&Vec<u8>, usize> = HashMap::new();
+        let mut groups: HashMap<String, HashMap<&Vec<u8>, usize>> = HashMap::new();
+        for (variant, case_ids) in variant_cases {
+            *overall.entry(variant).or_insert(0) += case_ids.len();
+            for case_id in case_ids {
+                let value = case_attributes.get(case_id).and_then(|attributes| attributes.get(attribute));
+                *groups.entry(group_of(value, bands)).or_default().entry(variant).or_insert(0) += 1;
+            }
+        }
+        let cases: usize = overall.values().sum();
+        let overall_share: HashMap<Vec<u8>, f64> = overall.iter().map(|(variant, count)| ((*variant).clone(), *count as f64 / cases as f64)).collect();
+
+        let mut groups: Vec<Group> = groups
+            .into_iter()
+            .map(|(value, counts)| {
+                let variants = sorted(counts);
+                let group_cases: usize = variants.iter().map(|(_, count)| count).sum();
+                let divergence = variants
+                    .iter()
+                    .map(|(variant, count)| {
+                        let p = *count as f64 / group_cases as f64;
+                        p * (p / overall_share[variant]).log2()
+                    })
+                    .sum::<f64>()
+                    .max(0.0);
+                Group { value, cases: group_cases, variants, divergence }
+            })
+            .collect();
+        groups.sort_unstable_by(|a, b| b.cases.cmp(&a.cases).then_with(|| a.value.cmp(&b.value)));
+
+        let variants = sorted(overall);
+        let divergence = groups.iter().map(|group| group.cases as f64 / cases as f64 * group.divergence).sum::<f64>();
+        let variant_entropy = entropy(&variants, cases);
+        Breakdown {
+            attribute: attribute.to_string(),
+            cases,
+            variants,
+            groups,
+            divergence,
+            normalized_divergence: if variant_entropy > 0.0 { (divergence / variant_entropy).min(1.0) } else { 0.0 },
+        }
+    }
+
+    pub fn to_json(&self, top: usize, with_names: bool) -> Value {
+        let top_variants = |variants: &[(Vec<u8>, usize)]| -> Value {
+            let top_n = variants.iter().take(top);
+            match with_names {
+                true => json!(activities::convert_variants_to_strings(top_n.collect())),
+                false => json!(top_n.collect::<Vec<_>>()),
+            }
+        };
+        json!({
+            "attribute": self.attribute,
+            "cases": self.cases,
+            "variants": self.variants.len(),
+            "top": top_variants(&self.variants),
+            "divergence": self.divergence,
+            "normalized_divergence": self.normalized_divergence,
+            "groups": self.groups
+                .iter()
+                .map(|group| json!({
+                    "value": group.value,
+                    "cases": group.cases,
+                    "share": group.cases as f64 / self.cases as f64,
+                    "variants": group.variants.len(),
+                    "top": top_variants(&group.variants),
+                    "divergence": group.divergence,
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
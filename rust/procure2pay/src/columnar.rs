@@ -0,0 +1,208 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  columnar: Apache Parquet input and output (through Arrow record batches)
+//          * reads case/activity/timestamp columns directly into encoded events for the parallel
+//            engine (case ids are interned, dictionary-encoded activities are converted once)
+//          * writes variants and case-to-variant assignments as Parquet
+//
+use crate::activities;
+use crate::csv_parser::Event;
+use crate::parallel::EncodedEvent;
+use crate::timezones::{self, Zone};
+
+use arrow_array::builder::{ListBuilder, StringBuilder};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Int32Type, Int64Type, TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType, TimestampSecondType,
+};
+use arrow_array::{Array, ArrayRef, Int64Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::{DateTime, NaiveDateTime};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::{ArrowWriter, ProjectionMask};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Write;
+use std::fs::File;
+use std::sync::Arc;
+
+// Column names of the input
+pub struct Columns<'a> {
+    pub case_id: &'a str,
+    pub activity: &'a str,
+    pub timestamp: &'a str,
+    // zone of the timestamps without one (strings without an offset, timestamps without a time zone)
+    pub zone: Zone,
+}
+
+// Encoded events with the case ids they refer to
+pub struct EncodedLog {
+    pub case_ids: Vec<String>,
+    pub events: Vec<EncodedEvent>,
+}
+
+impl EncodedLog {
+    // Back to plain events (for the golden engine and the other analyses)
+    pub fn decode(&self) -> Vec<Event> {
+        self.events
+            .iter()
+            .map(|(case, timestamp, activity)| (self.case_ids[*case as usize].clone(), *timestamp, activities::num_to_str(*activity).to_string()))
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct CaseInterner {
+    indices: HashMap<String, u32>,
+    case_ids: Vec<String>,
+}
+
+impl CaseInterner {
+    fn intern(&mut self, case_id: &str) -> u32 {
+        if let Some(&index) = self.indices.get(case_id) {
+            return index;
+        }
+        let index = self.case_ids.len() as u32;
+        self.indices.insert(case_id.to_string(), index);
+        self.case_ids.push(case_id.to_string());
+        index
+    }
+}
+
+// Reads one value of a string column (Utf8 or LargeUtf8)
+fn str_value(array: &dyn Array, row: usize) -> Result<&str, Box<dyn Error>> {
+    if array.is_null(row) {
+        return Err(format!("Unexpected null value in row {}", row).into());
+    }
+    if let Some(strings) = array.as_string_opt::<i32>() {
+        return Ok(strings.value(row));
+    }
+    if let Some(strings) = array.as_string_opt::<i64>() {
+        return Ok(strings.value(row));
+    }
+    Err(format!("Unsupported column type: {}", array.data_type()).into())
+}
+
+fn timestamp_value(array: &dyn Array, row: usize, zone: &Zone) -> Result<NaiveDateTime, Box<dyn Error>> {
+    if array.is_null(row) {
+        return Err(format!("Unexpected null timestamp in row {}", row).into());
+    }
+    let datetime = match array.data_type() {
+        DataType::Timestamp(TimeUnit::Second, _) => DateTime::from_timestamp(array.as_primitive::<TimestampSecondType>().value(row), 0),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => DateTime::from_timestamp_millis(array.as_primitive::<TimestampMillisecondType>().value(row)),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => DateTime::from_timestamp_micros(array.as_primitive::<TimestampMicrosecondType>().value(row)),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => Some(DateTime::from_timestamp_nanos(array.as_primitive::<TimestampNanosecondType>().value(row))),
+        _ => return timezones::parse_timestamp(str_value(array, row)?, zone),
+    };
+    let datetime = datetime.ok_or("Timestamp out of range")?.naive_utc();
+    // Timestamps with a time zone are UTC, the others local times
+    Ok(match array.data_type() {
+        DataType::Timestamp(_, None) => zone.to_utc(datetime),
+        _ => datetime,
+    })
+}
+
+fn encode_batch(batch: &RecordBatch, columns: &Columns, interner: &mut CaseInterner, events: &mut Vec<EncodedEvent>) -> Result<(), Box<dyn Error>> {
+    let column = |name: &str| batch.column_by_name(name).ok_or_else(|| format!("Missing column: {}", name));
+    let (case_ids, activity_names, timestamps) = (column(columns.case_id)?, column(columns.activity)?, column(columns.timestamp)?);
+
+    // Dictionary encoded activities are converted once per dictionary entry
+    let dictionary = match activity_names.as_any_dictionary_opt() {
+        Some(dictionary) => {
+            let values = dictionary.values();
+            let numbers = (0..values.len())
+                .map(|i| activities::try_str_to_num(str_value(values.as_ref(), i)?).ok_or("Unknown activity".into()))
+                .collect::<Result<Vec<u8>, Box<dyn Error>>>()?;
+            Some((dictionary.normalized_keys(), numbers))
+        }
+        None => None,
+    };
+
+    let mut buffer = String::new();
+    for row in 0..batch.num_rows() {
+        let case = match case_ids.data_type() {
+            DataType::Int32 | DataType::Int64 => {
+                if case_ids.is_null(row) {
+                    return Err(format!("Unexpected null case id in row {}", row).into());
+                }
+                buffer.clear();
+                match case_ids.as_primitive_opt::<Int64Type>() {
+                    Some(numbers) => write!(buffer, "{}", numbers.value(row))?,
+                    None => write!(buffer, "{}", case_ids.as_primitive::<Int32Type>().value(row))?,
+                }
+                interner.intern(&buffer)
+            }
+            _ => interner.intern(str_value(case_ids.as_ref(), row)?),
+        };
+        let activity = match &dictionary {
+            // (the key under a null is arbitrary, it may even be out of the dictionary)
+            Some(_) if activity_names.is_null(row) => return Err(format!("Unexpected null activity in row {}", row).into()),
+            Some((keys, numbers)) => numbers[keys[row]],
+            None => activities::try_str_to_num(str_value(activity_names.as_ref(), row)?).ok_or("Unknown activity")?,
+        };
+        events.push((case, timestamp_value(timestamps.as_ref(), row, &columns.zone)?, activity));
+    }
+    Ok(())
+}
+
+pub fn read_events(file_path: &str, columns: &Columns) -> Result<EncodedLog, Box<dyn Error>> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(file_path)?)?;
+    // Reads only the three columns we need
+    let roots = [columns.case_id, columns.activity, columns.timestamp]
+        .iter()
+        .map(|name| builder.schema().index_of(name))
+        .collect::<Result<Vec<usize>, _>>()?;
+    let projection = ProjectionMask::roots(builder.parquet_schema(), roots);
+    let reader = builder.with_projection(projection).build()?;
+
+    let mut interner = CaseInterner::default();
+    let mut events = Vec::new();
+    for batch in reader {
+        encode_batch(&batch?, columns, &mut interner, &mut events)?;
+    }
+    Ok(EncodedLog { case_ids: interner.case_ids, events })
+}
+
+fn write_batch(file_path: &str, batch: RecordBatch) -> Result<(), Box<dyn Error>> {
+    let mut writer = ArrowWriter::try_new(File::create(file_path)?, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+// Writes the sorted variants: variant_id (1-based rank), cases, activities (list of names)
+pub fn write_variants(file_path: &str, variants: &[(Vec<u8>, usize)]) -> Result<(), Box<dyn Error>> {
+    let mut names = ListBuilder::new(StringBuilder::new());
+    for (variant, _) in variants {
+        for &num in variant {
+            names.values().append_value(activities::num_to_str(num));
+        }
+        names.append(true);
+    }
+    let names = names.finish();
+    let schema = Schema::new(vec![
+        Field::new("variant_id", DataType::Int64, false),
+        Field::new("cases", DataType::Int64, false),
+        Field::new("activities", names.data_type().clone(), false),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int64Array::from_iter_values((1..=variants.len()).map(|id| id as i64))),
+        Arc::new(Int64Array::from_iter_values(variants.iter().map(|(_, count)| *count as i64))),
+        Arc::new(names),
+    ];
+    write_batch(file_path, RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+// Writes the case-to-variant assignments: case_id, variant_id
+pub fn write_cases(file_path: &str, assignments: &[(String, usize)]) -> Result<(), Box<dyn Error>> {
+    let schema = Schema::new(vec![
+        Field::new("case_id", DataType::Utf8, false),
+        Field::new("variant_id", DataType::Int64, false),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(assignments.iter().map(|(case_id, _)| case_id))),
+        Arc::new(Int64Array::from_iter_values(assignments.iter().map(|(_, variant_id)| *variant_id as i64))),
+    ];
+    write_batch(file_path, RecordBatch::try_new(Arc::new(schema), columns)?)
+}
@@ -0,0 +1,209 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  config: Analysis configuration file (TOML) with named profiles
+//          * `[profiles.NAME]` describe the input (path, format, columns, query, time zone), activity
+//            renames and label mapping, dedup policy, filters, engine and outputs
+//          * a profile can extend another one (its keys override those of the base)
+//          * `profile = "NAME"` at the top selects the profile used without --profile
+//          * a profile is translated into command line arguments, so the flags given on the
+//            command line override it (repeatable ones, e.g. filters, add up)
+//
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Input {
+    pub path: Option<String>,
+    // csv, parquet or sqlite
+    pub format: Option<String>,
+    pub case_column: Option<String>,
+    pub activity_column: Option<String>,
+    pub timestamp_column: Option<String>,
+    pub query: Option<String>,
+    // time zone of the timestamps without an offset
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Filters {
+    pub contains: Option<Vec<String>>,
+    pub excludes: Option<Vec<String>>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Output {
+    pub top: Option<usize>,
+    pub with_names: Option<bool>,
+    pub report: Option<String>,
+    pub assignments: Option<String>,
+    pub parquet_variants: Option<String>,
+    pub parquet_cases: Option<String>,
+    pub persist: Option<String>,
+    // time zone the times are shown in
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub extends: Option<String>,
+    pub input: Input,
+    // activity label in the log -> activity name used by the analysis
+    pub activities: BTreeMap<String, String>,
+    // label mapping file (see labels.rs) and level of detail (fine or coarse)
+    pub mapping: Option<String>,
+    pub level: Option<String>,
+    // consecutive or first-occurrence
+    pub dedup: Option<String>,
+    pub filters: Filters,
+    // parallel, gold or interned
+    pub engine: Option<String>,
+    pub output: Output,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub profile: Option<String>,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+pub fn parse(text: &str) -> Result<Config, Box<dyn Error>> {
+    Ok(toml::from_str(text)?)
+}
+
+pub fn load(file_path: &str) -> Result<Config, Box<dyn Error>> {
+    let text = std::fs::read_to_string(file_path).map_err(|err| format!("{}: {}", file_path, err))?;
+    parse(&text).map_err(|err| format!("{}: {}", file_path, err).into())
+}
+
+impl Config {
+    // The profile (or the default one) with the keys of the profiles it extends
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile, Box<dyn Error>> {
+        let name = match name.or(self.profile.as_deref()) {
+            Some(name) => name,
+            None if self.profiles.len() == 1 => self.profiles.keys().next().unwrap(),
+            None => return Err(format!("Select a profile with --profile ({})", self.names().join(", ")).into()),
+        };
+        let mut chain = vec![name.to_string()];
+        let mut profile = self.get(name)?.clone();
+        while let Some(base) = profile.extends.take() {
+            if chain.contains(&base) {
+                return Err(format!("Profile {} extends itself", base).into());
+            }
+            profile = profile.over(self.get(&base)?);
+            chain.push(base);
+        }
+        Ok(profile)
+    }
+
+    fn get(&self, name: &str) -> Result<&Profile, Box<dyn Error>> {
+        self.profiles.get(name).ok_or_else(|| format!("Unknown profile {} (available: {})", name, self.names().join(", ")).into())
+    }
+
+    fn names(&self) -> Vec<&str> {
+        self.profiles.keys().map(String::as_str).collect()
+    }
+}
+
+impl Profile {
+    // This profile with the keys it doesn't set taken from the base
+    fn over(self, base: &Profile) -> Profile {
+        let base = base.clone();
+        let mut activities = base.activities;
+        activities.extend(self.activities);
+        Profile {
+            extends: base.extends,
+            input: Input {
+                path: self.input.path.or(base.input.path),
+                format: self.input.format.or(base.input.format),
+                case_column: self.input.case_column.or(base.input.case_column),
+                activity_column: self.input.activity_column.or(base.input.activity_column),
+                timestamp_column: self.input.timestamp_column.or(base.input.timestamp_column),
+                query: self.input.query.or(base.input.query),
+                timezone: self.input.timezone.or(base.input.timezone),
+            },
+            activities,
+            mapping: self.mapping.or(base.mapping),
+            level: self.level.or(base.level),
+            dedup: self.dedup.or(base.dedup),
+            filters: Filters {
+                contains: self.filters.contains.or(base.filters.contains),
+                excludes: self.filters.excludes.or(base.filters.excludes),
+                from: self.filters.from.or(base.filters.from),
+                to: self.filters.to.or(base.filters.to),
+            },
+            engine: self.engine.or(base.engine),
+            output: Output {
+                top: self.output.top.or(base.output.top),
+                with_names: self.output.with_names.or(base.output.with_names),
+                report: self.output.report.or(base.output.report),
+                assignments: self.output.assignments.or(base.output.assignments),
+                parquet_variants: self.output.parquet_variants.or(base.output.parquet_variants),
+                parquet_cases: self.output.parquet_cases.or(base.output.parquet_cases),
+                persist: self.output.persist.or(base.output.persist),
+                timezone: self.output.timezone.or(base.output.timezone),
+            },
+        }
+    }
+
+    // The command line arguments of the profile (except the input path)
+    pub fn to_args(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut args = Vec::new();
+        let mut option = |name: &str, value: &Option<String>| {
+            if let Some(value) = value {
+                args.push(format!("--{}={}", name, value));
+            }
+        };
+        option("case-column", &self.input.case_column);
+        option("activity-column", &self.input.activity_column);
+        option("timestamp-column", &self.input.timestamp_column);
+        option("sqlite-query", &self.input.query);
+        option("source-tz", &self.input.timezone);
+        option("mapping", &self.mapping);
+        option("level", &self.level);
+        option("dedup", &self.dedup);
+        option("from", &self.filters.from);
+        option("to", &self.filters.to);
+        option("top", &self.output.top.map(|top| top.to_string()));
+        option("report", &self.output.report);
+        option("assignments", &self.output.assignments);
+        option("parquet-variants", &self.output.parquet_variants);
+        option("parquet-cases", &self.output.parquet_cases);
+        option("persist", &self.output.persist);
+        option("display-tz", &self.output.timezone);
+
+        match self.input.format.as_deref() {
+            None | Some("csv") => {}
+            Some("parquet") => args.push("--parquet".to_string()),
+            Some("sqlite") => args.push("--sqlite".to_string()),
+            Some(format) => return Err(format!("Unknown input format (csv, parquet or sqlite): {}", format).into()),
+        }
+        match self.engine.as_deref() {
+            None | Some("parallel") => {}
+            Some("gold") => args.push("--gold".to_string()),
+            Some("interned") => args.push("--interned".to_string()),
+            Some(engine) => return Err(format!("Unknown engine (parallel, gold or interned): {}", engine).into()),
+        }
+        if self.output.with_names == Some(true) {
+            args.push("--with-names".to_string());
+        }
+        for (label, name) in &self.activities {
+            args.push(format!("--rename={}={}", label, name));
+        }
+        for activity in self.filters.contains.iter().flatten() {
+            args.push(format!("--contains={}", activity));
+        }
+        for activity in self.filters.excludes.iter().flatten() {
+            args.push(format!("--excludes={}", activity));
+        }
+        Ok(args)
+    }
+}
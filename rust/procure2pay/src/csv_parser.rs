@@ -1,28 +1,50 @@
+use crate::timezones::{self, Zone};
+
 use chrono::NaiveDateTime;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-pub fn parse_csv(file_path: &str) -> Result<Vec<(String, NaiveDateTime, String)>, Box<dyn Error>> {
+// A single event of the log: (case_id, timestamp, activity_name)
+pub type Event = (String, NaiveDateTime, String);
+
+// Additional columns of an event (column name from the header -> value)
+pub type Attributes = HashMap<String, String>;
+
+pub fn parse_csv(file_path: &str) -> Result<Vec<Event>, Box<dyn Error>> {
+    parse_csv_in(file_path, &Zone::utc())
+}
+
+// Same as parse_csv(), but from any reader (e.g. bytes in memory)
+pub fn parse_csv_from<R: BufRead>(reader: R) -> Result<Vec<Event>, Box<dyn Error>> {
+    parse_csv_from_in(reader, &Zone::utc())
+}
+
+// Same as parse_csv(), with the timestamps without an offset in the time zone of the source
+// (all timestamps are converted to UTC, see timezones.rs)
+pub fn parse_csv_in(file_path: &str, zone: &Zone) -> Result<Vec<Event>, Box<dyn Error>> {
     let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
+    parse_csv_from_in(BufReader::new(file), zone)
+}
 
+pub fn parse_csv_from_in<R: BufRead>(reader: R, zone: &Zone) -> Result<Vec<Event>, Box<dyn Error>> {
     let mut cases = Vec::new();
-    let mut first_line = true;
+    let mut columns = 0;
     for line in reader.lines() {
         let line = line?;
         let fields: Vec<&str> = line.split(';').collect();
 
-        if first_line {
-            first_line = false;
+        if columns == 0 {
+            columns = fields.len().max(3);
             continue;
         }
-        if fields.len() != 3 {
+        if fields.len() != columns {
             continue; // Skip malformed lines
         }
 
         let case_id = fields[0].to_string();
-        let timestamp = NaiveDateTime::parse_from_str(fields[2], "%Y-%m-%d %H:%M:%S%.f")?;
+        let timestamp = timezones::parse_timestamp(fields[2], zone)?;
         let activity_name = fields[1].to_string();
 
         cases.push((case_id, timestamp, activity_name));
@@ -30,3 +52,37 @@ pub fn parse_csv(file_path: &str) -> Result<Vec<(String, NaiveDateTime, String)>
 
     Ok(cases)
 }
+
+// Same as parse_csv_in(), but keeps all columns after case_id, activity and timestamp
+// (e.g. user, department) as attributes of the event
+pub fn parse_csv_with_attributes(file_path: &str, zone: &Zone) -> Result<Vec<(Event, Attributes)>, Box<dyn Error>> {
+    let file = File::open(file_path)?;
+    let reader = BufReader::new(file);
+
+    let mut events = Vec::new();
+    let mut header: Option<Vec<String>> = None;
+    for line in reader.lines() {
+        let line = line?;
+        let fields: Vec<&str> = line.split(';').collect();
+
+        let names = match &header {
+            Some(names) => names,
+            None => {
+                header = Some(fields.iter().map(|name| name.trim().to_string()).collect());
+                continue;
+            }
+        };
+        if fields.len() != names.len() || fields.len() < 3 {
+            continue; // Skip malformed lines
+        }
+
+        let case_id = fields[0].to_string();
+        let timestamp = timezones::parse_timestamp(fields[2], zone)?;
+        let activity_name = fields[1].to_string();
+        let attributes = names[3..].iter().cloned().zip(fields[3..].iter().map(|value| value.to_string())).collect();
+
+        events.push(((case_id, timestamp, activity_name), attributes));
+    }
+
+    Ok(events)
+}
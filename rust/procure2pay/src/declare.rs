@@ -0,0 +1,271 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  declare: Declarative business rules (DECLARE templates)
+//          * discovers constraints from the log (with support and confidence thresholds)
+//          * loads rules from a file (one rule per line, e.g. `precedence(A, B)`)
+//          * checks the rules per case and reports the violating cases and variants
+//
+use crate::activities;
+use crate::traces::Trace;
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Template {
+    // Unary templates (on activity A)
+    Existence,
+    Absence,
+    ExactlyOnce,
+    Init,
+    End,
+    // Binary templates (on activities A and B)
+    RespondedExistence,
+    CoExistence,
+    Response,
+    Precedence,
+    Succession,
+    ChainResponse,
+    NotCoExistence,
+}
+
+const UNARY_TEMPLATES: [Template; 5] = [
+    Template::Existence, Template::Absence, Template::ExactlyOnce, Template::Init, Template::End,
+];
+
+const BINARY_TEMPLATES: [Template; 7] = [
+    Template::RespondedExistence, Template::CoExistence, Template::Response, Template::Precedence,
+    Template::Succession, Template::ChainResponse, Template::NotCoExistence,
+];
+
+impl Template {
+    fn name(&self) -> &'static str {
+        match self {
+            Template::Existence => "existence",
+            Template::Absence => "absence",
+            Template::ExactlyOnce => "exactly_once",
+            Template::Init => "init",
+            Template::End => "end",
+            Template::RespondedExistence => "responded_existence",
+            Template::CoExistence => "co_existence",
+            Template::Response => "response",
+            Template::Precedence => "precedence",
+            Template::Succession => "succession",
+            Template::ChainResponse => "chain_response",
+            Template::NotCoExistence => "not_co_existence",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Template> {
+        UNARY_TEMPLATES.iter().chain(BINARY_TEMPLATES.iter()).copied().find(|t| t.name() == name)
+    }
+
+    fn is_unary(&self) -> bool {
+        UNARY_TEMPLATES.contains(self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    pub template: Template,
+    pub a: u8,
+    // Unused for unary templates
+    pub b: u8,
+}
+
+// Outcome of a constraint on one trace: activated (not vacuous) and satisfied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outcome {
+    pub activated: bool,
+    pub satisfied: bool,
+}
+
+impl Constraint {
+    pub fn evaluate(&self, trace: &[u8]) -> Outcome {
+        let (a, b) = (self.a, self.b);
+        let count_a = trace.iter().filter(|&&x| x == a).count();
+        let has_a = count_a > 0;
+        let has_b = trace.contains(&b);
+        match self.template {
+            Template::Existence => Outcome { activated: true, satisfied: has_a },
+            Template::Absence => Outcome { activated: true, satisfied: !has_a },
+            Template::ExactlyOnce => Outcome { activated: true, satisfied: count_a == 1 },
+            Template::Init => Outcome { activated: true, satisfied: trace.first() == Some(&a) },
+            Template::End => Outcome { activated: true, satisfied: trace.last() == Some(&a) },
+            Template::RespondedExistence => Outcome { activated: has_a, satisfied: !has_a || has_b },
+            Template::CoExistence => Outcome { activated: has_a || has_b, satisfied: has_a == has_b },
+            Template::Response => Outcome { activated: has_a, satisfied: is_response(trace, a, b) },
+            Template::Precedence => Outcome { activated: has_b, satisfied: is_precedence(trace, a, b) },
+            Template::Succession => Outcome {
+                activated: has_a || has_b,
+                satisfied: is_response(trace, a, b) && is_precedence(trace, a, b),
+            },
+            Template::ChainResponse => Outcome {
+                activated: has_a,
+                // every A is immediately followed by B
+                satisfied: trace.iter().enumerate().all(|(i, &x)| x != a || trace.get(i + 1) == Some(&b)),
+            },
+            Template::NotCoExistence => Outcome { activated: has_a || has_b, satisfied: !(has_a && has_b) },
+        }
+    }
+}
+
+// Every A is eventually followed by B
+fn is_response(trace: &[u8], a: u8, b: u8) -> bool {
+    match trace.iter().rposition(|&x| x == a) {
+        Some(last_a) => trace[last_a + 1..].contains(&b),
+        None => true,
+    }
+}
+
+// Every B is preceded by some A
+fn is_precedence(trace: &[u8], a: u8, b: u8) -> bool {
+    match trace.iter().position(|&x| x == b) {
+        Some(first_b) => trace[..first_b].contains(&a),
+        None => true,
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.template.is_unary() {
+            write!(f, "{}({})", self.template.name(), activities::num_to_str(self.a))
+        } else {
+            write!(f, "{}({}, {})", self.template.name(), activities::num_to_str(self.a), activities::num_to_str(self.b))
+        }
+    }
+}
+
+// Parses one rule, e.g. `precedence(Enter goods receipt, Post invoice in MM)`
+// (activity names may contain parentheses, but not commas)
+pub fn parse_rule(rule: &str) -> Result<Constraint, Box<dyn Error>> {
+    let rule = rule.trim();
+    let (open, close) = match (rule.find('('), rule.rfind(')')) {
+        (Some(open), Some(close)) if open < close => (open, close),
+        _ => return Err(format!("Invalid rule syntax: {}", rule).into()),
+    };
+    let name = rule[..open].trim();
+    let template = Template::from_name(name).ok_or_else(|| format!("Unknown template: {}", name))?;
+    let args = rule[open + 1..close]
+        .split(',')
+        .map(|arg| activities::try_str_to_num(arg.trim()).ok_or_else(|| format!("Unknown activity: {}", arg.trim())))
+        .collect::<Result<Vec<u8>, String>>()?;
+
+    match (template.is_unary(), args.as_slice()) {
+        (true, [a]) => Ok(Constraint { template, a: *a, b: *a }),
+        (false, [a, b]) => Ok(Constraint { template, a: *a, b: *b }),
+        _ => Err(format!("Wrong number of activities for {}: {}", name, rule).into()),
+    }
+}
+
+// Loads rules from a file (empty lines and lines starting with '#' are skipped)
+pub fn load_rules(file_path: &str) -> Result<Vec<Constraint>, Box<dyn Error>> {
+    fs::read_to_string(file_path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(parse_rule)
+        .collect()
+}
+
+// Groups traces by their variant, so the constraints are evaluated once per variant
+fn variant_counts(traces: &[Trace]) -> Vec<(&[u8], usize)> {
+    let mut counts: HashMap<&[u8], usize> = HashMap::new();
+    for trace in traces {
+        *counts.entry(trace.activities.as_slice()).or_insert(0) += 1;
+    }
+    counts.into_iter().collect()
+}
+
+// Discovers constraints over activities seen in the log:
+// * support: share of all cases activating and satisfying the constraint
+// * confidence: share of activated cases satisfying the constraint
+pub fn mine(traces: &[Trace], min_support: f64, min_confidence: f64) -> Vec<(Constraint, f64, f64)> {
+    let variants = variant_counts(traces);
+    let mut seen: Vec<u8> = variants.iter().flat_map(|(variant, _)| variant.iter().copied()).collect();
+    seen.sort_unstable();
+    seen.dedup();
+
+    let mut candidates = Vec::new();
+    for &a in &seen {
+        for template in UNARY_TEMPLATES {
+            candidates.push(Constraint { template, a, b: a });
+        }
+        for &b in seen.iter().filter(|&&b| b != a) {
+            for template in BINARY_TEMPLATES {
+                candidates.push(Constraint { template, a, b });
+            }
+        }
+    }
+
+    let total = traces.len() as f64;
+    let mut mined = Vec::new();
+    for constraint in candidates {
+        let (mut activated, mut fulfilled) = (0, 0);
+        for (variant, count) in &variants {
+            let outcome = constraint.evaluate(variant);
+            if outcome.activated {
+                activated += count;
+                if outcome.satisfied {
+                    fulfilled += count;
+                }
+            }
+        }
+        if activated == 0 {
+            continue;
+        }
+        let support = fulfilled as f64 / total;
+        let confidence = fulfilled as f64 / activated as f64;
+        if support >= min_support && confidence >= min_confidence {
+            mined.push((constraint, support, confidence));
+        }
+    }
+    mined.sort_by(|a, b| {
+        b.1.total_cmp(&a.1)
+            .then_with(|| b.2.total_cmp(&a.2))
+            .then_with(|| a.0.to_string().cmp(&b.0.to_string()))
+    });
+    mined
+}
+
+pub fn mined_to_json(mined: &[(Constraint, f64, f64)]) -> Value {
+    json!(mined
+        .iter()
+        .map(|(constraint, support, confidence)| json!({
+            "constraint": constraint.to_string(),
+            "support": support,
+            "confidence": confidence,
+        }))
+        .collect::<Vec<_>>())
+}
+
+// Checks all rules per case and lists the violating cases and variants (with names)
+pub fn check(traces: &[Trace], rules: &[Constraint]) -> Value {
+    let results = rules
+        .iter()
+        .map(|rule| {
+            let mut cases = Vec::new();
+            let mut variants: HashMap<&[u8], usize> = HashMap::new();
+            for trace in traces.iter().filter(|trace| !rule.evaluate(&trace.activities).satisfied) {
+                cases.push(trace.case_id.as_str());
+                *variants.entry(trace.activities.as_slice()).or_insert(0) += 1;
+            }
+            let mut variants: Vec<_> = variants.into_iter().collect();
+            variants.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+            let variants: Vec<_> = variants
+                .into_iter()
+                .map(|(variant, count)| (variant.iter().map(|&num| activities::num_to_str(num)).collect::<Vec<_>>(), count))
+                .collect();
+            json!({
+                "rule": rule.to_string(),
+                "violations": cases.len(),
+                "cases": cases,
+                "variants": variants,
+            })
+        })
+        .collect::<Vec<_>>();
+    json!(results)
+}
@@ -0,0 +1,72 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  dfg: Directly-follows graph of the traces
+//          * activity occurrences and their mean relative position in the traces (for layouts)
+//          * directly-follows edges, start and end activities with their frequencies
+//
+use crate::activities;
+use crate::traces::Trace;
+
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Dfg {
+    // activity -> (occurrences, mean relative position in the trace, 0..1]
+    pub activities: BTreeMap<u8, (usize, f64)>,
+    // (from, to) -> number of times `to` directly followed `from`
+    pub edges: BTreeMap<(u8, u8), usize>,
+    // activity -> number of cases starting (ending) with it
+    pub start: BTreeMap<u8, usize>,
+    pub end: BTreeMap<u8, usize>,
+}
+
+pub fn discover(traces: &[Trace]) -> Dfg {
+    let mut dfg = Dfg::default();
+    for trace in traces {
+        for (i, &activity) in trace.activities.iter().enumerate() {
+            let entry = dfg.activities.entry(activity).or_insert((0, 0.0));
+            entry.0 += 1;
+            entry.1 += (i + 1) as f64 / trace.activities.len() as f64;
+        }
+        for pair in trace.activities.windows(2) {
+            *dfg.edges.entry((pair[0], pair[1])).or_insert(0) += 1;
+        }
+        if let (Some(&first), Some(&last)) = (trace.activities.first(), trace.activities.last()) {
+            *dfg.start.entry(first).or_insert(0) += 1;
+            *dfg.end.entry(last).or_insert(0) += 1;
+        }
+    }
+    for (count, position) in dfg.activities.values_mut() {
+        *position /= *count as f64;
+    }
+    dfg
+}
+
+impl Dfg {
+    // Activities ordered by their mean position (roughly from the start to the end of the process)
+    pub fn ordered_activities(&self) -> Vec<u8> {
+        let mut ordered: Vec<u8> = self.activities.keys().copied().collect();
+        ordered.sort_by(|a, b| self.activities[a].1.total_cmp(&self.activities[b].1).then_with(|| a.cmp(b)));
+        ordered
+    }
+
+    pub fn to_json(&self) -> Value {
+        let counts = |counts: &BTreeMap<u8, usize>| {
+            counts.iter().map(|(&activity, count)| json!({"activity": activities::num_to_str(activity), "count": count})).collect::<Vec<_>>()
+        };
+        json!({
+            "activities": self.ordered_activities()
+                .iter()
+                .map(|activity| json!({"activity": activities::num_to_str(*activity), "count": self.activities[activity].0}))
+                .collect::<Vec<_>>(),
+            "edges": self.edges
+                .iter()
+                .map(|((from, to), count)| json!({"from": activities::num_to_str(*from), "to": activities::num_to_str(*to), "count": count}))
+                .collect::<Vec<_>>(),
+            "start": counts(&self.start),
+            "end": counts(&self.end),
+        })
+    }
+}
@@ -0,0 +1,309 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  explore: Interactive terminal explorer of variants (`procure2pay explore log.csv`)
+//          * ranked variant list (parallel engine, activity names as printed with --with-names)
+//          * a variant expanded into its activities with mean elapsed and waiting times
+//          * the cases of a variant with their start, end and throughput time
+//          * case filters (see filters.rs) entered interactively, variants recomputed on change
+//          * the state and key handling (`Explorer`) are separate from the drawing (`draw`)
+//
+use crate::activities;
+use crate::assignments::VariantCases;
+use crate::csv_parser::Event;
+use crate::filters::Filters;
+use crate::parallel;
+use crate::timezones::{self, Zone};
+use crate::traces::{self, Trace};
+
+use ratatui::crossterm::event::{self, Event as TerminalEvent, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState};
+use ratatui::Frame;
+use std::collections::HashMap;
+use std::error::Error;
+
+const HELP_VARIANTS: &str = "↑↓ select  Enter details  c cases  f filter  r reset filters  q quit";
+const HELP_DETAILS: &str = "c cases  Esc back  q quit";
+const HELP_CASES: &str = "↑↓ scroll  Esc back  q quit";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum View {
+    Variants,
+    Details,
+    Cases,
+}
+
+pub struct Explorer {
+    events: Vec<Event>,
+    // case_id -> trace (filters keep or drop whole cases, so the traces never change)
+    traces: HashMap<String, Trace>,
+    pub filters: Filters,
+    // of the times of the filters without an offset, and of the times shown (UTC without an offset if None)
+    zone: Zone,
+    display_zone: Option<Zone>,
+    // variants of the filtered events with their case ids, and the same with activity names
+    pub variants: VariantCases,
+    pub names: Vec<(Vec<String>, usize)>,
+    pub view: View,
+    pub selected: usize,
+    pub selected_case: usize,
+    // filter being typed (as NAME=VALUE, e.g. contains=Set payment block)
+    pub input: Option<String>,
+    pub message: String,
+    pub quit: bool,
+    // rows scrolled by PageUp/PageDown (the height of the last drawn table)
+    page: usize,
+}
+
+// Human readable duration, e.g. 3d 4h, 5h 12m, 40m
+pub fn format_duration(seconds: f64) -> String {
+    let minutes = (seconds / 60.0).round() as i64;
+    match (minutes / 1440, minutes % 1440 / 60, minutes % 60) {
+        (0, 0, m) => format!("{}m", m),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
+
+impl Explorer {
+    // An error for an unknown activity (the filtered events are known to be fine afterwards)
+    pub fn new(events: Vec<Event>, zone: Zone, display_zone: Option<Zone>) -> Result<Explorer, Box<dyn Error>> {
+        let traces = traces::build_traces(events.clone())?.into_iter().map(|trace| (trace.case_id.clone(), trace)).collect();
+        let mut explorer = Explorer {
+            events,
+            traces,
+            filters: Filters::default(),
+            zone,
+            display_zone,
+            variants: Vec::new(),
+            names: Vec::new(),
+            view: View::Variants,
+            selected: 0,
+            selected_case: 0,
+            input: None,
+            message: String::new(),
+            quit: false,
+            page: 10,
+        };
+        explorer.refresh();
+        Ok(explorer)
+    }
+
+    // A time as shown in the views
+    pub fn display(&self, utc: chrono::NaiveDateTime) -> String {
+        timezones::display(utc, self.display_zone.as_ref())
+    }
+
+    // Recomputes the variants of the filtered events
+    fn refresh(&mut self) {
+        self.variants = parallel::process_cases_with_case_ids(self.filters.apply(&self.events)).expect("Activities checked by new()");
+        let counts: Vec<(Vec<u8>, usize)> = self.variants.iter().map(|(variant, cases)| (variant.clone(), cases.len())).collect();
+        self.names = activities::convert_variants_to_strings(counts.iter().collect());
+        self.selected = 0;
+        self.selected_case = 0;
+        self.view = View::Variants;
+        let cases: usize = self.variants.iter().map(|(_, cases)| cases.len()).sum();
+        self.message = format!("{} variants, {} cases", self.variants.len(), cases);
+    }
+
+    // Traces of the cases of the selected variant
+    pub fn selected_traces(&self) -> Vec<&Trace> {
+        match self.variants.get(self.selected) {
+            Some((_, cases)) => cases.iter().filter_map(|case_id| self.traces.get(case_id)).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Activities of the selected variant with the mean elapsed time since the start of the case
+    // and the mean waiting time since the previous activity (in seconds)
+    pub fn details(&self) -> Vec<(String, f64, f64)> {
+        let traces = self.selected_traces();
+        let names = match self.names.get(self.selected) {
+            Some((names, _)) => names,
+            None => return Vec::new(),
+        };
+        let mean = |seconds: &dyn Fn(&Trace) -> i64| {
+            traces.iter().map(|trace| seconds(trace) as f64).sum::<f64>() / traces.len().max(1) as f64
+        };
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let elapsed = mean(&|trace| (trace.timestamps[i] - trace.timestamps[0]).num_seconds());
+                let waiting = mean(&|trace| (trace.timestamps[i] - trace.timestamps[i.saturating_sub(1)]).num_seconds());
+                (name.clone(), elapsed, waiting)
+            })
+            .collect()
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let (selected, len) = match self.view {
+            View::Cases => (&mut self.selected_case, self.variants.get(self.selected).map_or(0, |(_, cases)| cases.len())),
+            _ => (&mut self.selected, self.variants.len()),
+        };
+        *selected = (*selected as isize + delta).clamp(0, len.saturating_sub(1) as isize) as usize;
+    }
+
+    fn apply_filter(&mut self, input: &str) {
+        let parameter = match input.split_once('=') {
+            Some((name, value)) if ["contains", "excludes", "from", "to"].contains(&name.trim()) => (name.trim().to_string(), value.trim().to_string()),
+            _ => {
+                self.message = format!("Invalid filter (contains=, excludes=, from= or to=): {}", input);
+                return;
+            }
+        };
+        let mut parameters = self.filter_parameters();
+        parameters.push(parameter);
+        match Filters::parse(&parameters, &self.zone) {
+            Ok(filters) => {
+                self.filters = filters;
+                self.refresh();
+            }
+            Err(err) => self.message = err.to_string(),
+        }
+    }
+
+    // The current filters as (name, value) pairs (times with an offset, so they parse again to the same time)
+    pub fn filter_parameters(&self) -> Vec<(String, String)> {
+        let zone = self.display_zone.as_ref().unwrap_or(&self.zone);
+        let time = |name: &str, time: Option<chrono::NaiveDateTime>| time.map(|time| (name.to_string(), zone.format(time)));
+        self.filters.contains.iter().map(|activity| ("contains".to_string(), activity.clone()))
+            .chain(self.filters.excludes.iter().map(|activity| ("excludes".to_string(), activity.clone())))
+            .chain(time("from", self.filters.from))
+            .chain(time("to", self.filters.to))
+            .collect()
+    }
+
+    pub fn on_key(&mut self, key: KeyCode) {
+        // Typing a filter
+        if let Some(input) = self.input.as_mut() {
+            match key {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Enter => {
+                    let input = self.input.take().unwrap_or_default();
+                    self.apply_filter(&input);
+                }
+                KeyCode::Esc => self.input = None,
+                _ => {}
+            }
+            return;
+        }
+        match (self.view, key) {
+            (_, KeyCode::Char('q')) => self.quit = true,
+            (_, KeyCode::Up | KeyCode::Char('k')) => self.move_selection(-1),
+            (_, KeyCode::Down | KeyCode::Char('j')) => self.move_selection(1),
+            (_, KeyCode::PageUp) => self.move_selection(-(self.page as isize)),
+            (_, KeyCode::PageDown) => self.move_selection(self.page as isize),
+            (_, KeyCode::Home) => self.move_selection(isize::MIN / 2),
+            (_, KeyCode::End) => self.move_selection(isize::MAX / 2),
+            (View::Variants, KeyCode::Enter | KeyCode::Right) if !self.variants.is_empty() => self.view = View::Details,
+            (View::Variants | View::Details, KeyCode::Char('c')) if !self.variants.is_empty() => {
+                self.selected_case = 0;
+                self.view = View::Cases;
+            }
+            (View::Variants, KeyCode::Char('f')) => self.input = Some(String::new()),
+            (View::Variants, KeyCode::Char('r')) => {
+                self.filters = Filters::default();
+                self.refresh();
+            }
+            (View::Details | View::Cases, KeyCode::Esc | KeyCode::Left | KeyCode::Backspace) => self.view = View::Variants,
+            _ => {}
+        }
+    }
+}
+
+fn table<'a>(title: String, header: Vec<&'a str>, rows: Vec<Row<'a>>, widths: Vec<Constraint>) -> Table<'a> {
+    Table::new(rows, widths)
+        .header(Row::new(header).style(Style::new().add_modifier(Modifier::BOLD)))
+        .block(Block::bordered().title(title))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+}
+
+pub fn draw(frame: &mut Frame, explorer: &mut Explorer) {
+    let [main, status] = Layout::vertical([Constraint::Min(3), Constraint::Length(2)]).areas(frame.area());
+    // Rows of the table without its borders and header
+    explorer.page = main.height.saturating_sub(3).max(1) as usize;
+    let total: usize = explorer.names.iter().map(|(_, count)| count).sum();
+
+    match explorer.view {
+        View::Variants => {
+            let rows = explorer.names
+                .iter()
+                .enumerate()
+                .map(|(rank, (names, count))| {
+                    Row::new(vec![
+                        (rank + 1).to_string(),
+                        count.to_string(),
+                        format!("{:.1} %", 100.0 * *count as f64 / total.max(1) as f64),
+                        names.join(" → "),
+                    ])
+                })
+                .collect();
+            let widths = vec![Constraint::Length(5), Constraint::Length(8), Constraint::Length(8), Constraint::Fill(1)];
+            let mut state = TableState::new().with_selected(Some(explorer.selected));
+            frame.render_stateful_widget(table("Variants".to_string(), vec!["#", "Cases", "Share", "Activities"], rows, widths), main, &mut state);
+        }
+        View::Details => {
+            let rows = explorer.details()
+                .into_iter()
+                .map(|(name, elapsed, waiting)| Row::new(vec![name, format_duration(elapsed), format_duration(waiting)]))
+                .collect();
+            let widths = vec![Constraint::Fill(1), Constraint::Length(12), Constraint::Length(12)];
+            let title = format!("Variant {} ({} cases)", explorer.selected + 1, explorer.names[explorer.selected].1);
+            frame.render_widget(table(title, vec!["Activity", "Elapsed", "Waiting"], rows, widths), main);
+        }
+        View::Cases => {
+            // The case ids of a variant are sorted
+            let rows = explorer.selected_traces()
+                .iter()
+                .map(|trace| {
+                    let (start, end) = (trace.timestamps[0], *trace.timestamps.last().unwrap());
+                    Row::new(vec![trace.case_id.clone(), explorer.display(start), explorer.display(end), format_duration((end - start).num_seconds() as f64)])
+                })
+                .collect();
+            let widths = vec![Constraint::Fill(1), Constraint::Length(26), Constraint::Length(26), Constraint::Length(12)];
+            let mut state = TableState::new().with_selected(Some(explorer.selected_case));
+            let title = format!("Cases of variant {}", explorer.selected + 1);
+            frame.render_stateful_widget(table(title, vec!["Case", "Start", "End", "Throughput"], rows, widths), main, &mut state);
+        }
+    }
+
+    let filters: Vec<String> = explorer.filter_parameters().iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+    let first = match &explorer.input {
+        Some(input) => format!("Filter: {}▏", input),
+        None if filters.is_empty() => explorer.message.clone(),
+        None => format!("{} | {}", explorer.message, filters.join(", ")),
+    };
+    let help = match (&explorer.input, explorer.view) {
+        (Some(_), _) => "contains=ACTIVITY, excludes=ACTIVITY, from=DATE or to=DATE  Enter apply  Esc cancel",
+        (None, View::Variants) => HELP_VARIANTS,
+        (None, View::Details) => HELP_DETAILS,
+        (None, View::Cases) => HELP_CASES,
+    };
+    frame.render_widget(Paragraph::new(vec![Line::from(first), Line::from(help).style(Style::new().add_modifier(Modifier::DIM))]), status);
+}
+
+// Runs the explorer in the terminal until quit
+pub fn run(events: Vec<Event>, zone: Zone, display_zone: Option<Zone>) -> Result<(), Box<dyn Error>> {
+    let mut explorer = Explorer::new(events, zone, display_zone)?;
+    let mut terminal = ratatui::init();
+    let result = (|| -> Result<(), Box<dyn Error>> {
+        while !explorer.quit {
+            terminal.draw(|frame| draw(frame, &mut explorer))?;
+            if let TerminalEvent::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    explorer.on_key(key.code);
+                }
+            }
+        }
+        Ok(())
+    })();
+    ratatui::restore();
+    result
+}
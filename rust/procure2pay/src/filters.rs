@@ -0,0 +1,99 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  filters: Case filters applied to the events before the analysis
+//          * cases containing (all of) some activities, or none of some others
+//          * cases starting within a time range
+//          * a case is kept or dropped as a whole (with all its events)
+//          * the first-occurrence dedup policy (preprocessing of the events)
+//
+use crate::csv_parser::Event;
+use crate::timezones::{self, Zone};
+
+use chrono::NaiveDateTime;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Filters {
+    // activities every kept case contains
+    pub contains: Vec<String>,
+    // activities no kept case contains
+    pub excludes: Vec<String>,
+    // the first event of a kept case is at or after `from` and before `to`
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+// A local time of the zone of the source, unless the time has an offset (the timestamps of the
+// events are UTC, see timezones.rs)
+fn parse_time(value: &str, zone: &Zone) -> Result<NaiveDateTime, Box<dyn Error>> {
+    timezones::parse_timestamp(value, zone)
+        .or_else(|_| timezones::parse_timestamp(&format!("{} 00:00:00", value), zone))
+        .map_err(|_| format!("Invalid time (expected YYYY-MM-DD[ HH:MM:SS][+HH:MM]): {}", value).into())
+}
+
+impl Filters {
+    // Builds the filters from (name, value) pairs (e.g. query parameters), times without an offset
+    // in the zone; the other names are ignored
+    pub fn parse(parameters: &[(String, String)], zone: &Zone) -> Result<Filters, Box<dyn Error>> {
+        let mut filters = Filters::default();
+        for (name, value) in parameters {
+            match name.as_str() {
+                "contains" => filters.contains.push(value.clone()),
+                "excludes" => filters.excludes.push(value.clone()),
+                "from" => filters.from = Some(parse_time(value, zone)?),
+                "to" => filters.to = Some(parse_time(value, zone)?),
+                _ => {}
+            }
+        }
+        Ok(filters)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Filters::default()
+    }
+
+    // The events of the cases passing all filters
+    pub fn apply(&self, events: &[Event]) -> Vec<Event> {
+        if self.is_empty() {
+            return events.to_vec();
+        }
+        // case_id -> (first timestamp, activities)
+        let mut cases: HashMap<&str, (NaiveDateTime, HashSet<&str>)> = HashMap::new();
+        for (case_id, timestamp, activity) in events {
+            let case = cases.entry(case_id).or_insert((*timestamp, HashSet::new()));
+            case.0 = case.0.min(*timestamp);
+            case.1.insert(activity);
+        }
+        let kept: HashSet<&str> = cases
+            .into_iter()
+            .filter(|(_, (start, activities))| {
+                self.contains.iter().all(|activity| activities.contains(activity.as_str()))
+                    && !self.excludes.iter().any(|activity| activities.contains(activity.as_str()))
+                    && self.from.is_none_or(|from| *start >= from)
+                    && self.to.is_none_or(|to| *start < to)
+            })
+            .map(|(case_id, _)| case_id)
+            .collect();
+        events.iter().filter(|event| kept.contains(event.0.as_str())).cloned().collect()
+    }
+}
+
+// Keeps only the first event of each activity in a case (repeated activities, e.g. rework,
+// are dropped even if not consecutive)
+pub fn first_occurrences(mut events: Vec<Event>) -> Vec<Event> {
+    // Same ordering as the engines (case_id, timestamp, activity name)
+    events.sort_unstable_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)).then_with(|| a.2.cmp(&b.2)));
+    let mut kept = Vec::with_capacity(events.len());
+    let mut seen: HashSet<String> = HashSet::new();
+    for event in events {
+        if kept.last().is_some_and(|last: &Event| last.0 != event.0) {
+            seen.clear();
+        }
+        if seen.insert(event.2.clone()) {
+            kept.push(event);
+        }
+    }
+    kept
+}
@@ -0,0 +1,337 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  generator: Synthetic event logs (for benchmarking and testing)
+//          * cases drawn from a variant distribution or simulated on a Petri net
+//          * case arrivals and activity durations from configurable distributions
+//          * noise injection (swapped, missing and duplicated events)
+//          * deterministic for a given seed
+//
+use crate::activities;
+use crate::csv_parser::Event;
+
+use chrono::{Duration, NaiveDateTime};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+
+// Small deterministic PRNG (SplitMix64), so the generated logs don't depend on any crate version
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Uniform in [0, n)
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_f64() * n as f64) as usize
+    }
+
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+
+    // Picks an index with probability proportional to its weight
+    pub fn weighted(&mut self, weights: &[f64]) -> usize {
+        let mut target = self.next_f64() * weights.iter().sum::<f64>();
+        for (i, weight) in weights.iter().enumerate() {
+            if target < *weight {
+                return i;
+            }
+            target -= weight;
+        }
+        weights.len() - 1
+    }
+
+    pub fn standard_normal(&mut self) -> f64 {
+        // Box-Muller transform
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+// Distribution of a duration in seconds
+#[derive(Debug, Clone, PartialEq)]
+pub enum Distribution {
+    Fixed(f64),
+    Uniform(f64, f64),
+    Exponential(f64),
+    Normal(f64, f64),
+    LogNormal(f64, f64),
+}
+
+impl Distribution {
+    // Parses e.g. `fixed:60`, `uniform:60:3600`, `exp:86400`, `normal:3600:600`, `lognormal:10:1`
+    pub fn parse(spec: &str) -> Result<Distribution, Box<dyn Error>> {
+        let mut parts = spec.split(':');
+        let kind = parts.next().unwrap_or_default();
+        let params = parts.map(|p| p.parse::<f64>()).collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| format!("Invalid distribution parameters: {}", spec))?;
+        match (kind, params.as_slice()) {
+            ("fixed", [value]) => Ok(Distribution::Fixed(*value)),
+            ("uniform", [min, max]) if min <= max => Ok(Distribution::Uniform(*min, *max)),
+            ("exp", [mean]) => Ok(Distribution::Exponential(*mean)),
+            ("normal", [mean, sd]) => Ok(Distribution::Normal(*mean, *sd)),
+            ("lognormal", [mu, sigma]) => Ok(Distribution::LogNormal(*mu, *sigma)),
+            _ => Err(format!("Invalid distribution: {}", spec).into()),
+        }
+    }
+
+    // Samples a (non-negative) number of seconds
+    pub fn sample(&self, rng: &mut Rng) -> f64 {
+        let value = match *self {
+            Distribution::Fixed(value) => value,
+            Distribution::Uniform(min, max) => min + (max - min) * rng.next_f64(),
+            Distribution::Exponential(mean) => -mean * (1.0 - rng.next_f64()).ln(),
+            Distribution::Normal(mean, sd) => mean + sd * rng.standard_normal(),
+            Distribution::LogNormal(mu, sigma) => (mu + sigma * rng.standard_normal()).exp(),
+        };
+        value.max(0.0)
+    }
+}
+
+pub struct Transition {
+    // None for silent transitions (not recorded in the log)
+    pub label: Option<String>,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub weight: f64,
+}
+
+pub struct PetriNet {
+    pub transitions: Vec<Transition>,
+    pub initial: HashMap<String, usize>,
+    pub final_marking: HashMap<String, usize>,
+}
+
+pub enum Model {
+    // Activity sequences with their weights
+    Variants(Vec<(Vec<String>, f64)>),
+    PetriNet(PetriNet),
+}
+
+// Probability (per event) of each kind of noise
+#[derive(Debug, Clone, Default)]
+pub struct Noise {
+    pub swap: f64,
+    pub missing: f64,
+    pub duplicate: f64,
+}
+
+pub struct Config {
+    pub cases: usize,
+    pub seed: u64,
+    pub start: NaiveDateTime,
+    // Time between two case arrivals
+    pub arrival: Distribution,
+    // Time before each activity (per activity name, or the default)
+    pub durations: HashMap<String, Distribution>,
+    pub default_duration: Distribution,
+    pub noise: Noise,
+    pub shuffle: bool,
+}
+
+pub fn check_activity(name: &str) -> Result<String, Box<dyn Error>> {
+    activities::try_str_to_num(name).ok_or_else(|| format!("Unknown activity: {}", name))?;
+    Ok(name.to_string())
+}
+
+// Loads variants from a file in the same format procure2pay prints with `--with-names`
+// i.e. `[[["activity", ...], count], ...]`
+pub fn load_variants(file_path: &str) -> Result<Model, Box<dyn Error>> {
+    let json: Value = serde_json::from_str(&fs::read_to_string(file_path)?)?;
+    let mut variants = Vec::new();
+    for variant in json.as_array().ok_or("Variants must be a JSON array")? {
+        let (sequence, count) = match variant.as_array().map(|v| v.as_slice()) {
+            Some([sequence, count]) => (sequence, count),
+            _ => return Err(format!("Invalid variant: {}", variant).into()),
+        };
+        let sequence = sequence
+            .as_array()
+            .ok_or_else(|| format!("Invalid variant: {}", variant))?
+            .iter()
+            .map(|name| check_activity(name.as_str().ok_or("Activity must be a string")?))
+            .collect::<Result<Vec<_>, _>>()?;
+        let count = count.as_f64().ok_or_else(|| format!("Invalid variant count: {}", count))?;
+        variants.push((sequence, count));
+    }
+    if variants.is_empty() {
+        return Err("No variants to generate from".into());
+    }
+    Ok(Model::Variants(variants))
+}
+
+// Loads a Petri net from JSON:
+// `{"transitions": [{"label": "...", "inputs": ["p0"], "outputs": ["p1"], "weight": 1.0}, ...],
+//   "initial": {"p0": 1}, "final": {"p9": 1}}` (label null = silent transition, weight is optional
+//   and positive)
+pub fn load_petri_net(file_path: &str) -> Result<Model, Box<dyn Error>> {
+    let json: Value = serde_json::from_str(&fs::read_to_string(file_path)?)?;
+    // Each place at most once (an arc carries one token)
+    let places = |value: &Value| -> Result<Vec<String>, Box<dyn Error>> {
+        let mut places: Vec<String> = Vec::new();
+        for place in value.as_array().ok_or("Places must be a JSON array")? {
+            let place = place.as_str().ok_or("Place must be a string")?;
+            if places.iter().any(|other| other == place) {
+                return Err(format!("Duplicate place of a transition: {}", place).into());
+            }
+            places.push(place.to_string());
+        }
+        Ok(places)
+    };
+    let marking = |value: &Value| -> Result<HashMap<String, usize>, Box<dyn Error>> {
+        value.as_object()
+            .ok_or("Marking must be a JSON object")?
+            .iter()
+            .map(|(place, tokens)| Ok((place.clone(), tokens.as_u64().ok_or("Tokens must be a number")? as usize)))
+            .collect()
+    };
+
+    let mut transitions = Vec::new();
+    for transition in json["transitions"].as_array().ok_or("Missing transitions")? {
+        transitions.push(Transition {
+            label: match transition["label"].as_str() {
+                Some(label) => Some(check_activity(label)?),
+                None => None,
+            },
+            inputs: places(&transition["inputs"])?,
+            outputs: places(&transition["outputs"])?,
+            weight: match &transition["weight"] {
+                Value::Null => 1.0,
+                weight => weight.as_f64().filter(|weight| *weight > 0.0 && weight.is_finite()).ok_or_else(|| format!("Invalid weight (must be positive): {}", weight))?,
+            },
+        });
+    }
+    Ok(Model::PetriNet(PetriNet { transitions, initial: marking(&json["initial"])?, final_marking: marking(&json["final"])? }))
+}
+
+impl PetriNet {
+    // Plays the token game until the final marking is reached, an error if no transition is enabled
+    // before (a deadlock) or if it takes more than MAX_STEPS transitions (e.g. a loop which never ends)
+    fn simulate(&self, rng: &mut Rng) -> Result<Vec<String>, Box<dyn Error>> {
+        const MAX_STEPS: usize = 1000;
+        let mut marking = self.initial.clone();
+        let mut trace = Vec::new();
+        let tokens = |marking: &HashMap<String, usize>, place: &String| marking.get(place).copied().unwrap_or(0);
+
+        for step in 0..=MAX_STEPS {
+            let reached_final = self.final_marking.iter().all(|(place, &count)| tokens(&marking, place) >= count);
+            if reached_final {
+                break;
+            }
+            // A transition takes one token from each input place (see load_petri_net)
+            let enabled: Vec<usize> = (0..self.transitions.len())
+                .filter(|&i| self.transitions[i].inputs.iter().all(|place| tokens(&marking, place) > 0))
+                .collect();
+            if enabled.is_empty() {
+                return Err(format!("The Petri net deadlocked before reaching its final marking (after {} transitions)", step).into());
+            }
+            if step == MAX_STEPS {
+                return Err(format!("The Petri net didn't reach its final marking within {} transitions", MAX_STEPS).into());
+            }
+            let transition = &self.transitions[enabled[rng.weighted(&enabled.iter().map(|&i| self.transitions[i].weight).collect::<Vec<_>>())]];
+            for place in &transition.inputs {
+                *marking.get_mut(place).unwrap() -= 1;
+            }
+            for place in &transition.outputs {
+                *marking.entry(place.clone()).or_insert(0) += 1;
+            }
+            if let Some(label) = &transition.label {
+                trace.push(label.clone());
+            }
+        }
+        Ok(trace)
+    }
+}
+
+impl Model {
+    pub fn sample_trace(&self, rng: &mut Rng) -> Result<Vec<String>, Box<dyn Error>> {
+        match self {
+            Model::Variants(variants) => {
+                let weights: Vec<f64> = variants.iter().map(|(_, weight)| *weight).collect();
+                Ok(variants[rng.weighted(&weights)].0.clone())
+            }
+            Model::PetriNet(net) => net.simulate(rng),
+        }
+    }
+}
+
+fn after(timestamp: NaiveDateTime, seconds: f64) -> NaiveDateTime {
+    timestamp + Duration::milliseconds((seconds * 1000.0) as i64)
+}
+
+pub fn generate(model: &Model, config: &Config) -> Result<Vec<Event>, Box<dyn Error>> {
+    let mut rng = Rng::new(config.seed);
+    let mut events = Vec::new();
+    let mut arrival = config.start;
+
+    for case in 1..=config.cases {
+        let case_id = case.to_string();
+        let trace = model.sample_trace(&mut rng)?;
+        let mut timestamp = arrival;
+        let mut case_events: Vec<Event> = Vec::with_capacity(trace.len());
+        for (i, activity) in trace.into_iter().enumerate() {
+            if i > 0 {
+                let duration = config.durations.get(&activity).unwrap_or(&config.default_duration);
+                timestamp = after(timestamp, duration.sample(&mut rng));
+            }
+            case_events.push((case_id.clone(), timestamp, activity));
+        }
+
+        // Noise: swaps the activities of two subsequent events, drops or duplicates events
+        for i in 1..case_events.len() {
+            if rng.chance(config.noise.swap) {
+                let previous = std::mem::take(&mut case_events[i - 1].2);
+                case_events[i - 1].2 = std::mem::replace(&mut case_events[i].2, previous);
+            }
+        }
+        for event in case_events {
+            if rng.chance(config.noise.missing) {
+                continue;
+            }
+            if rng.chance(config.noise.duplicate) {
+                events.push(event.clone());
+            }
+            events.push(event);
+        }
+
+        arrival = after(arrival, config.arrival.sample(&mut rng));
+    }
+
+    if config.shuffle {
+        rng.shuffle(&mut events);
+    }
+    Ok(events)
+}
+
+// Writes the events in the same CSV format procure2pay reads
+pub fn write_csv<W: Write>(events: &[Event], writer: &mut W) -> std::io::Result<()> {
+    writeln!(writer, "case_id;activity;timestamp")?;
+    for (case_id, timestamp, activity) in events {
+        writeln!(writer, "{};{};{}", case_id, activity, timestamp.format("%Y-%m-%d %H:%M:%S%.3f"))?;
+    }
+    writer.flush()
+}
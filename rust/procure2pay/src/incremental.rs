@@ -0,0 +1,221 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  incremental: Append-only variant analysis with the engine state persisted between runs
+//          * keeps the events of open cases and the variant counts of finalized cases
+//          * a new batch only updates the cases it touches
+//          * a case is finalized once its last event is the end activity
+//          * events for already finalized cases (late events) are reported and ignored
+//
+use crate::activities;
+use crate::csv_parser::Event;
+
+use chrono::NaiveDateTime;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+const STATE_VERSION: u64 = 1;
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+pub struct State {
+    end_activity: u8,
+    // case_id -> events (timestamp, activity) of cases without the end activity yet
+    open: HashMap<String, Vec<(NaiveDateTime, u8)>>,
+    // finalized cases (only to recognize late events)
+    closed: HashSet<String>,
+    // variant counts of the finalized cases
+    variants: HashMap<Vec<u8>, usize>,
+}
+
+// What one batch of events changed
+#[derive(Debug, Default, PartialEq)]
+pub struct Update {
+    pub events: usize,
+    pub finalized: usize,
+    pub open_cases: usize,
+    pub late_events: usize,
+    pub late_cases: Vec<String>,
+}
+
+impl Update {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "events": self.events,
+            "finalized_cases": self.finalized,
+            "open_cases": self.open_cases,
+            "late_events": self.late_events,
+            "late_cases": self.late_cases,
+        })
+    }
+}
+
+impl State {
+    pub fn new(end_activity: u8) -> State {
+        State { end_activity, open: HashMap::new(), closed: HashSet::new(), variants: HashMap::new() }
+    }
+
+    // Loads the state, or starts with an empty one if the file doesn't exist yet
+    pub fn load_or_new(file_path: &str, end_activity: u8) -> Result<State, Box<dyn Error>> {
+        if !Path::new(file_path).exists() {
+            return Ok(State::new(end_activity));
+        }
+        let json: Value = serde_json::from_str(&fs::read_to_string(file_path)?)?;
+        if json["version"].as_u64() != Some(STATE_VERSION) {
+            return Err(format!("Unsupported state version: {}", json["version"]).into());
+        }
+        if json["end_activity"].as_str() != Some(activities::num_to_str(end_activity)) {
+            return Err(format!("The state was built with a different end activity: {}", json["end_activity"]).into());
+        }
+        let mut state = State::new(end_activity);
+
+        // Names unknown to the activity tables (e.g. categories of a label mapping) are registered
+        // again, the batch of this run may not have them
+        let activity = |value: &Value| -> Result<u8, Box<dyn Error>> {
+            activities::register(value.as_str().ok_or("Activity must be a string")?)
+        };
+        for (case_id, events) in json["open"].as_object().ok_or("Missing open cases")? {
+            let mut case_events = Vec::new();
+            for event in events.as_array().ok_or("Events must be a JSON array")? {
+                let timestamp = event[0].as_str().ok_or("Timestamp must be a string")?;
+                case_events.push((NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT)?, activity(&event[1])?));
+            }
+            state.open.insert(case_id.clone(), case_events);
+        }
+        for case_id in json["closed"].as_array().ok_or("Missing closed cases")? {
+            state.closed.insert(case_id.as_str().ok_or("Case id must be a string")?.to_string());
+        }
+        for variant in json["variants"].as_array().ok_or("Missing variants")? {
+            let sequence = variant[0]
+                .as_array()
+                .ok_or("Variant must be a JSON array")?
+                .iter()
+                .map(activity)
+                .collect::<Result<Vec<u8>, _>>()?;
+            state.variants.insert(sequence, variant[1].as_u64().ok_or("Invalid variant count")? as usize);
+        }
+        Ok(state)
+    }
+
+    // Activities are stored by their names, so the state survives changes of the numbering
+    pub fn save(&self, file_path: &str) -> Result<(), Box<dyn Error>> {
+        let names = |variant: &[u8]| variant.iter().map(|&num| activities::num_to_str(num)).collect::<Vec<_>>();
+        let open: serde_json::Map<String, Value> = self.open
+            .iter()
+            .map(|(case_id, events)| {
+                let events: Vec<Value> = events
+                    .iter()
+                    .map(|(timestamp, activity)| json!([timestamp.format(TIMESTAMP_FORMAT).to_string(), activities::num_to_str(*activity)]))
+                    .collect();
+                (case_id.clone(), json!(events))
+            })
+            .collect();
+        let mut closed: Vec<&String> = self.closed.iter().collect();
+        closed.sort();
+        let json = json!({
+            "version": STATE_VERSION,
+            "end_activity": activities::num_to_str(self.end_activity),
+            "open": open,
+            "closed": closed,
+            "variants": self.top_variants().iter().map(|(variant, count)| json!([names(variant), count])).collect::<Vec<_>>(),
+        });
+        // Writes a temporary file first, so an interrupted run doesn't corrupt the state
+        let temporary = format!("{}.tmp", file_path);
+        fs::write(&temporary, json.to_string())?;
+        fs::rename(&temporary, file_path)?;
+        Ok(())
+    }
+
+    // Adds one event to its open case, returns false for a late event (its case is finalized already)
+    pub fn add_event(&mut self, case_id: &str, timestamp: NaiveDateTime, activity: u8) -> bool {
+        if self.closed.contains(case_id) {
+            return false;
+        }
+        match self.open.get_mut(case_id) {
+            Some(events) => events.push((timestamp, activity)),
+            None => {
+                self.open.insert(case_id.to_string(), vec![(timestamp, activity)]);
+            }
+        }
+        true
+    }
+
+    // Applies a batch of events and finalizes the cases that reached the end activity
+    pub fn update(&mut self, events: Vec<Event>) -> Result<Update, Box<dyn Error>> {
+        let mut update = Update { events: events.len(), ..Update::default() };
+        let mut touched = HashSet::new();
+        let mut late_cases = HashSet::new();
+        for (case_id, timestamp, activity_name) in events {
+            let activity = activities::try_str_to_num(&activity_name).ok_or_else(|| format!("Unknown activity: {}", activity_name))?;
+            if self.add_event(&case_id, timestamp, activity) {
+                touched.insert(case_id);
+            } else {
+                update.late_events += 1;
+                late_cases.insert(case_id);
+            }
+        }
+        for case_id in touched {
+            if self.close_if_ended(&case_id) {
+                update.finalized += 1;
+            }
+        }
+        update.open_cases = self.open_cases();
+        update.late_cases = late_cases.into_iter().collect();
+        update.late_cases.sort();
+        Ok(update)
+    }
+
+    // Finalizes the case if its last event is the end activity
+    pub fn close_if_ended(&mut self, case_id: &str) -> bool {
+        let ended = self.open
+            .get(case_id)
+            .and_then(|events| events.iter().max_by(|a, b| order(a, b)))
+            .is_some_and(|(_, activity)| *activity == self.end_activity);
+        ended && self.finalize(case_id)
+    }
+
+    // Finalizes the open cases with no event since `before` (timed out cases), returns their number
+    pub fn expire_idle(&mut self, before: NaiveDateTime) -> usize {
+        let idle: Vec<String> = self.open
+            .iter()
+            .filter(|(_, events)| events.iter().all(|(timestamp, _)| *timestamp < before))
+            .map(|(case_id, _)| case_id.clone())
+            .collect();
+        for case_id in &idle {
+            self.finalize(case_id);
+        }
+        idle.len()
+    }
+
+    pub fn open_cases(&self) -> usize {
+        self.open.len()
+    }
+
+    // Counts the variant of an open case (if it exists) and marks it as closed
+    pub fn finalize(&mut self, case_id: &str) -> bool {
+        let mut events = match self.open.remove(case_id) {
+            Some(events) => events,
+            None => return false,
+        };
+        events.sort_by(order);
+        let mut variant: Vec<u8> = events.into_iter().map(|(_, activity)| activity).collect();
+        variant.dedup();
+        *self.variants.entry(variant).or_insert(0) += 1;
+        self.closed.insert(case_id.to_string());
+        true
+    }
+
+    // Variants of the finalized cases, sorted by count (and by the variant for the same counts)
+    pub fn top_variants(&self) -> Vec<(Vec<u8>, usize)> {
+        let mut sorted_variants: Vec<_> = self.variants.iter().map(|(variant, count)| (variant.clone(), *count)).collect();
+        sorted_variants.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        sorted_variants
+    }
+}
+
+// The same ordering the engines use: by timestamp, then by activity name
+fn order(a: &(NaiveDateTime, u8), b: &(NaiveDateTime, u8)) -> std::cmp::Ordering {
+    a.0.cmp(&b.0).then_with(|| activities::num_to_str(a.1).cmp(activities::num_to_str(b.1)))
+}
@@ -0,0 +1,191 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  interned: Allocation-free variant counting engine
+//          * interns case ids (borrowed strings -> u32) and converts activities once per event
+//          * sorts compact (case, timestamp, activity rank) events instead of strings
+//          * hashes each variant incrementally while de-duplicating its activities into
+//            a reused buffer; only new variants are copied into an arena
+//          * per-thread tables (hash -> arena offset) are merged by hash
+//
+use crate::activities;
+
+use chrono::NaiveDateTime;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::error::Error;
+
+// Variants with their number of cases, the most frequent first
+type Variants = Vec<(Vec<u8>, usize)>;
+
+// FNV-1a, so a variant can be hashed one activity at a time
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn hash_activity(hash: u64, activity: u8) -> u64 {
+    (hash ^ activity as u64).wrapping_mul(FNV_PRIME)
+}
+
+#[derive(Clone, Copy, Default)]
+struct Slot {
+    hash: u64,
+    offset: u32,
+    len: u32,
+    // 0 = empty slot
+    count: usize,
+}
+
+// Open addressing table of variants: the slots point into one arena of activities
+struct VariantTable {
+    arena: Vec<u8>,
+    slots: Vec<Slot>,
+    used: usize,
+}
+
+impl VariantTable {
+    fn new() -> VariantTable {
+        VariantTable { arena: Vec::new(), slots: vec![Slot::default(); 64], used: 0 }
+    }
+
+    fn variant(&self, slot: &Slot) -> &[u8] {
+        &self.arena[slot.offset as usize..(slot.offset + slot.len) as usize]
+    }
+
+    fn add(&mut self, variant: &[u8], hash: u64, count: usize) {
+        if (self.used + 1) * 4 > self.slots.len() * 3 {
+            self.grow();
+        }
+        let mask = self.slots.len() - 1;
+        let mut index = hash as usize & mask;
+        loop {
+            let slot = self.slots[index];
+            if slot.count == 0 {
+                let offset = self.arena.len() as u32;
+                self.arena.extend_from_slice(variant);
+                self.slots[index] = Slot { hash, offset, len: variant.len() as u32, count };
+                self.used += 1;
+                return;
+            }
+            if slot.hash == hash && self.variant(&slot) == variant {
+                self.slots[index].count += count;
+                return;
+            }
+            index = (index + 1) & mask;
+        }
+    }
+
+    // Doubles the slots (the arena stays as it is)
+    fn grow(&mut self) {
+        let size = self.slots.len() * 2;
+        let slots = std::mem::replace(&mut self.slots, vec![Slot::default(); size]);
+        let mask = self.slots.len() - 1;
+        for slot in slots.into_iter().filter(|slot| slot.count > 0) {
+            let mut index = slot.hash as usize & mask;
+            while self.slots[index].count > 0 {
+                index = (index + 1) & mask;
+            }
+            self.slots[index] = slot;
+        }
+    }
+
+    fn merge(&mut self, other: &VariantTable) {
+        for slot in other.slots.iter().filter(|slot| slot.count > 0) {
+            self.add(other.variant(slot), slot.hash, slot.count);
+        }
+    }
+
+    fn into_sorted(self) -> Vec<(Vec<u8>, usize)> {
+        let mut sorted_variants: Vec<_> = self.slots
+            .iter()
+            .filter(|slot| slot.count > 0)
+            .map(|slot| (self.variant(slot).to_vec(), slot.count))
+            .collect();
+        sorted_variants.sort_by_key(|v| std::cmp::Reverse(v.1));
+        sorted_variants
+    }
+}
+
+pub fn process_cases(raw_cases: Vec<(String, NaiveDateTime, String)>) -> Result<Variants, Box<dyn Error>> {
+    if raw_cases.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Interns the case ids (the map borrows them, no copies) in the order of their first event
+    let mut case_ids: HashMap<&str, u32> = HashMap::new();
+    let cases: Vec<u32> = raw_cases
+        .iter()
+        .map(|(case_id, _, _)| {
+            let next = case_ids.len() as u32;
+            *case_ids.entry(case_id.as_str()).or_insert(next)
+        })
+        .collect();
+    let activity_numbers: Vec<u8> = raw_cases
+        .par_iter()
+        .map(|(_, _, name)| activities::try_str_to_num(name).ok_or_else(|| format!("Unknown activity: {}", name)))
+        .collect::<Result<_, _>>()?;
+
+    // Activities with the same timestamp are ordered by their names (as in the other engines),
+    // so they're sorted by the rank of the name
+    let mut seen = [false; 256];
+    for &activity in &activity_numbers {
+        seen[activity as usize] = true;
+    }
+    let mut names: Vec<u8> = (0..=255).filter(|&activity| seen[activity as usize]).collect();
+    names.sort_by_key(|&activity| activities::num_to_str(activity));
+    let mut rank = [0u8; 256];
+    for (i, &activity) in names.iter().enumerate() {
+        rank[activity as usize] = i as u8;
+    }
+
+    let mut events: Vec<(u32, NaiveDateTime, u8, u8)> = raw_cases
+        .iter()
+        .zip(cases)
+        .zip(activity_numbers)
+        .map(|(((_, timestamp, _), case), activity)| (case, *timestamp, rank[activity as usize], activity))
+        .collect();
+    drop(case_ids);
+    drop(raw_cases);
+    events.par_sort_unstable_by_key(|event| (event.0, event.1, event.2));
+
+    // Chunks of roughly the same size, each processed into its own table
+    let threads = rayon::current_num_threads().max(1);
+    let chunk_size = events.len().div_ceil(threads).max(1024);
+    let mut bounds = vec![0];
+    while *bounds.last().unwrap() < events.len() {
+        let mut end = (bounds.last().unwrap() + chunk_size).min(events.len());
+        // Never splits a case
+        while end < events.len() && events[end].0 == events[end - 1].0 {
+            end += 1;
+        }
+        bounds.push(end);
+    }
+
+    let tables: Vec<VariantTable> = bounds
+        .windows(2)
+        .collect::<Vec<_>>()
+        .par_iter()
+        .map(|window| {
+            let mut table = VariantTable::new();
+            let mut variant: Vec<u8> = Vec::new();
+            for case in events[window[0]..window[1]].chunk_by(|a, b| a.0 == b.0) {
+                variant.clear();
+                let mut hash = FNV_OFFSET;
+                for event in case {
+                    if variant.last() != Some(&event.3) {
+                        variant.push(event.3);
+                        hash = hash_activity(hash, event.3);
+                    }
+                }
+                table.add(&variant, hash, 1);
+            }
+            table
+        })
+        .collect();
+
+    let mut tables = tables.into_iter();
+    let mut merged = tables.next().unwrap_or_else(VariantTable::new);
+    for table in tables {
+        merged.merge(&table);
+    }
+    Ok(merged.into_sorted())
+}
@@ -0,0 +1,143 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  labels: Activity label normalization and hierarchy mapping (before the variants are computed)
+//          * fine level: labels renamed, several labels merged into one (e.g. near-duplicates)
+//          * coarse level: the (renamed) activities grouped into higher-level categories by
+//            patterns with `*` wildcards, e.g. "Change PO" for "Change purchase order (*)"
+//          * names unknown to the activity tables are registered (see activities::register)
+//
+//  The mapping file (TOML):
+//
+//  [rename]
+//  "Change cash discount days 2" = "Change cash discount days"
+//  "Change cash discount days 1" = "Change cash discount days"
+//
+//  [categories]
+//  "Change PO" = ["Change purchase order (*)", "Change purchase order item"]
+//  "Post invoice" = ["Post invoice in *"]
+//
+use crate::activities;
+use crate::csv_parser::Event;
+
+use serde::de::{Deserializer, MapAccess, Visitor};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Fine,
+    Coarse,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Mapping {
+    // label -> activity name (the same name for several labels merges them)
+    pub rename: BTreeMap<String, String>,
+    // category -> patterns of the activities in it, in the order of the file (the first matching category wins)
+    #[serde(deserialize_with = "in_order")]
+    pub categories: Vec<(String, Vec<String>)>,
+}
+
+// Reads a table into its (key, value) pairs in the order of the file (toml keeps it, a map wouldn't)
+fn in_order<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, Vec<String>)>, D::Error> {
+    struct InOrder;
+
+    impl<'de> Visitor<'de> for InOrder {
+        type Value = Vec<(String, Vec<String>)>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a table of categories with their patterns")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut entries = Vec::new();
+            while let Some(entry) = map.next_entry()? {
+                entries.push(entry);
+            }
+            Ok(entries)
+        }
+    }
+
+    deserializer.deserialize_map(InOrder)
+}
+
+// Parses a rename: the label in the log and the activity name separated by '='
+pub fn parse_rename(rename: &str) -> Result<(String, String), Box<dyn Error>> {
+    match rename.split_once('=') {
+        Some((label, name)) if !label.trim().is_empty() && !name.trim().is_empty() => Ok((label.trim().to_string(), name.trim().to_string())),
+        _ => Err(format!("Invalid rename (expected \"LABEL=ACTIVITY\"): {}", rename).into()),
+    }
+}
+
+// Matches the label against a pattern where `*` stands for any (possibly empty) text
+pub fn matches(pattern: &str, label: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match label.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts: Vec<&str> = parts.collect();
+    match parts.split_last() {
+        // No wildcard at all
+        None => rest.is_empty(),
+        Some((last, middle)) => {
+            for part in middle {
+                match rest.find(part) {
+                    Some(i) => rest = &rest[i + part.len()..],
+                    None => return false,
+                }
+            }
+            rest.len() >= last.len() && rest.ends_with(last)
+        }
+    }
+}
+
+impl Mapping {
+    pub fn parse(text: &str) -> Result<Mapping, Box<dyn Error>> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn load(file_path: &str) -> Result<Mapping, Box<dyn Error>> {
+        let text = std::fs::read_to_string(file_path).map_err(|err| format!("{}: {}", file_path, err))?;
+        Mapping::parse(&text).map_err(|err| format!("{}: {}", file_path, err).into())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rename.is_empty() && self.categories.is_empty()
+    }
+
+    // The activity name of the label at the level of detail
+    pub fn map<'a>(&'a self, label: &'a str, level: Level) -> &'a str {
+        let name = self.rename.get(label).map(String::as_str).unwrap_or(label);
+        if level == Level::Fine {
+            return name;
+        }
+        self.categories
+            .iter()
+            .find(|(_, patterns)| patterns.iter().any(|pattern| matches(pattern, name)))
+            .map(|(category, _)| category.as_str())
+            .unwrap_or(name)
+    }
+
+    // Maps the activities of the events (each distinct label is looked up once)
+    pub fn apply(&self, events: &mut [Event], level: Level) -> Result<(), Box<dyn Error>> {
+        let mut mapped: HashMap<String, String> = HashMap::new();
+        for (_, _, activity) in events.iter_mut() {
+            if !mapped.contains_key(activity.as_str()) {
+                let name = self.map(activity, level).to_string();
+                // Merged and category names may be new to the activity tables
+                if name != *activity {
+                    activities::register(&name)?;
+                }
+                mapped.insert(activity.clone(), name);
+            }
+            activity.clone_from(&mapped[activity.as_str()]);
+        }
+        Ok(())
+    }
+}
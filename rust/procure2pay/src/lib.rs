@@ -0,0 +1,35 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  lib: The analysis modules, shared by the command line tool (main.rs) and the benchmarks
+//
+pub mod csv_parser;
+pub mod sequential;
+pub mod activities;
+pub mod parallel;
+pub mod traces;
+pub mod declare;
+pub mod resources;
+pub mod prediction;
+pub mod generator;
+pub mod simulation;
+pub mod sqlite;
+pub mod columnar;
+pub mod assignments;
+pub mod incremental;
+pub mod stream;
+pub mod out_of_core;
+pub mod interned;
+pub mod report;
+pub mod dfg;
+pub mod filters;
+pub mod server;
+pub mod explore;
+pub mod config;
+pub mod labels;
+pub mod timezones;
+pub mod breakdown;
+pub mod patterns;
+pub mod anomalies;
+pub mod transition_system;
+mod tests;
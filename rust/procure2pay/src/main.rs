@@ -6,26 +6,438 @@
 //          * calls the extract function to get top-variants
 //          * prints out the json
 //
-mod csv_parser;
-mod sequential;
-mod activities;
-mod parallel;
-mod tests;
 
+use procure2pay::{
+    activities, anomalies, assignments, breakdown, columnar, config, csv_parser, declare, explore, filters, generator, incremental,
+    interned, labels, out_of_core, parallel, patterns, prediction, report, resources, sequential, server, simulation, sqlite, stream,
+    timezones, traces, transition_system,
+};
 use std::time::Instant;
 use serde_json::json;
-use clap::{Arg, Command};
+use clap::{Arg, ArgGroup, ArgMatches, Command};
 
 fn main() {
-    let matches = Command::new("ProcureToPay")
+    let args: Vec<String> = std::env::args().collect();
+    let mut matches = command().get_matches_from(&args);
+
+    // The profile of the configuration file goes before the given arguments (which override it)
+    if let Some(config_path) = matches.get_one::<String>("config") {
+        let config = config::load(config_path).expect("Failed to load the configuration");
+        let mut profile = config.profile(matches.get_one::<String>("profile").map(String::as_str)).expect("Failed to select the profile");
+        // Engine and input format flags conflict with each other, the given ones replace those of the profile
+        if matches.get_flag("gold") || matches.get_flag("interned") {
+            profile.engine = None;
+        }
+        if matches.get_flag("sqlite") || matches.get_flag("parquet") {
+            profile.input.format = None;
+        }
+        let mut profile_args = vec![args[0].clone()];
+        if !matches.contains_id("file") {
+            profile_args.extend(profile.input.path.clone());
+        }
+        profile_args.extend(profile.to_args().expect("Invalid profile"));
+        profile_args.extend(args[1..].iter().cloned());
+        matches = command().get_matches_from(profile_args);
+    }
+
+    if let Some(("generate", generate_matches)) = matches.subcommand() {
+        generate(generate_matches);
+        return;
+    }
+    if let Some(("simulate", simulate_matches)) = matches.subcommand() {
+        simulate(simulate_matches);
+        return;
+    }
+    if let Some(("stream", stream_matches)) = matches.subcommand() {
+        run_stream(stream_matches);
+        return;
+    }
+    if let Some(("serve", serve_matches)) = matches.subcommand() {
+        serve(serve_matches);
+        return;
+    }
+    if let Some(("explore", explore_matches)) = matches.subcommand() {
+        let zone = explore_matches.get_one::<timezones::Zone>("source-tz").copied().unwrap_or_default();
+        let display_zone = explore_matches.get_one::<timezones::Zone>("display-tz").or(explore_matches.get_one::<timezones::Zone>("source-tz"));
+        let events = csv_parser::parse_csv_in(explore_matches.get_one::<String>("file").unwrap(), &zone).expect("Failed to parse CSV");
+        explore::run(events, zone, display_zone.copied()).expect("Failed to run the explorer");
+        return;
+    }
+    let file_path = match matches.get_one::<String>("file") {
+        Some(file_path) => file_path,
+        None => command().error(clap::error::ErrorKind::MissingRequiredArgument, "the <file> argument (or input.path of the profile) is required").exit(),
+    };
+    // Case attributes of SQLite and Parquet input come only from the case table (a CSV has its extra columns)
+    if matches.contains_id("group-by") && !matches.contains_id("case-table") && (matches.get_flag("sqlite") || matches.get_flag("parquet")) {
+        command().error(clap::error::ErrorKind::MissingRequiredArgument, "--group-by with --sqlite or --parquet input requires --case-table").exit();
+    }
+    // Timestamps are UTC from here on, displayed in the zone of the source unless another one is given
+    let source_zone = matches.get_one::<timezones::Zone>("source-tz").copied().unwrap_or_default();
+    let display_zone = matches.get_one::<timezones::Zone>("display-tz").or(matches.get_one::<timezones::Zone>("source-tz"));
+
+    if matches.get_flag("resources") {
+        let events = csv_parser::parse_csv_with_attributes(file_path, &source_zone).expect("Failed to parse CSV");
+        let sod_rules = matches
+            .get_many::<String>("sod")
+            .unwrap_or_default()
+            .map(|rule| resources::parse_sod_rule(rule).expect("Failed to parse segregation-of-duties rule"))
+            .collect::<Vec<_>>();
+        let resource_column = matches.get_one::<String>("resource-column").unwrap();
+        let analysis = resources::analyze(events, resource_column, &sod_rules, display_zone).expect("Failed to analyze resources");
+        if let Some(dot_path) = matches.get_one::<String>("dot") {
+            std::fs::write(dot_path, analysis.to_dot()).expect("Failed to write DOT file");
+        }
+        println!("{}", analysis.to_json());
+        std::process::exit(0);
+    }
+
+    // The renames given on the command line go on top of those of the mapping file
+    let mut mapping = match matches.get_one::<String>("mapping") {
+        Some(mapping_path) => labels::Mapping::load(mapping_path).expect("Failed to load the label mapping"),
+        None => labels::Mapping::default(),
+    };
+    mapping.rename.extend(
+        matches.get_many::<String>("rename").unwrap_or_default().map(|rename| labels::parse_rename(rename).expect("Failed to parse rename")),
+    );
+    let level = match matches.get_one::<String>("level").unwrap().as_str() {
+        "coarse" => labels::Level::Coarse,
+        _ => labels::Level::Fine,
+    };
+    let filter_parameters: Vec<(String, String)> = ["contains", "excludes", "from", "to"]
+        .iter()
+        .flat_map(|name| matches.get_many::<String>(name).unwrap_or_default().map(|value| (name.to_string(), value.clone())))
+        .collect();
+    let case_filters = filters::Filters::parse(&filter_parameters, &source_zone).expect("Failed to parse filters");
+    let first_occurrences = matches.get_one::<String>("dedup").unwrap() == "first-occurrence";
+    let preprocess = !mapping.is_empty() || !case_filters.is_empty() || first_occurrences;
+
+    // Keeps the events for case-to-variant assignments
+    let keep_cases = matches.contains_id("persist") || matches.contains_id("parquet-cases");
+    // Keeps the case ids of each variant (for --assignments)
+    let with_case_ids = matches.contains_id("assignments");
+    // Parquet input goes straight into the parallel engine as encoded events, unless
+    // the events themselves are needed (other analyses, golden and interned engines, assignments)
+    let needs_events = keep_cases
+        || with_case_ids
+        || preprocess
+        || ["crunch-activities", "declare-mine", "gold", "interned", "patterns"].iter().any(|flag| matches.get_flag(flag))
+        || ["declare-check", "predict", "state", "report", "group-by", "anomalies", "transition-system"].iter().any(|arg| matches.contains_id(arg));
+    let mut encoded_log = None;
+    // Attributes of the cases for --group-by (from the extra columns of a CSV and the case table)
+    let mut case_attributes = breakdown::CaseAttributes::new();
+
+    // Read and parse the CSV (or query the database, or read Parquet)
+    let mut cases = if matches.get_flag("out-of-core") {
+        Ok(Vec::new())  // read partition by partition later
+    } else if matches.get_flag("sqlite") {
+        sqlite::read_events(file_path, matches.get_one::<String>("sqlite-query").unwrap(), &source_zone)
+    } else if matches.get_flag("parquet") {
+        let columns = columnar::Columns {
+            case_id: matches.get_one::<String>("case-column").unwrap(),
+            activity: matches.get_one::<String>("activity-column").unwrap(),
+            timestamp: matches.get_one::<String>("timestamp-column").unwrap(),
+            zone: source_zone,
+        };
+        match columnar::read_events(file_path, &columns) {
+            Ok(log) if needs_events => Ok(log.decode()),
+            Ok(log) => {
+                encoded_log = Some(log);
+                Ok(Vec::new())
+            }
+            Err(err) => Err(err),
+        }
+    } else if matches.contains_id("group-by") {
+        csv_parser::parse_csv_with_attributes(file_path, &source_zone).map(|events| {
+            case_attributes = breakdown::from_events(&events);
+            events.into_iter().map(|(event, _)| event).collect()
+        })
+    } else {
+        csv_parser::parse_csv_in(file_path, &source_zone)
+    }.expect("Failed to read events");
+
+    // Label mapping, dedup policy and filters (in this order) before any analysis (also of the
+    // open cases of --predict, so that they match the training log)
+    let preprocess_events = |mut events: Vec<csv_parser::Event>| {
+        if !preprocess {
+            return events;
+        }
+        mapping.apply(&mut events, level).expect("Failed to map the activity labels");
+        if first_occurrences {
+            events = filters::first_occurrences(events);
+        }
+        if !case_filters.is_empty() {
+            events = case_filters.apply(&events);
+        }
+        events
+    };
+    cases = preprocess_events(cases);
+
+    if matches.get_flag("crunch-activities") {
+        activities::crunch_activities(cases);
+        std::process::exit(0);
+    }
+    if matches.get_flag("declare-mine") {
+        let min_support = *matches.get_one::<f64>("min-support").unwrap();
+        let min_confidence = *matches.get_one::<f64>("min-confidence").unwrap();
+        let mined = declare::mine(&traces::build_traces(cases).expect("Failed to build the traces"), min_support, min_confidence);
+        println!("{}", declare::mined_to_json(&mined));
+        std::process::exit(0);
+    }
+    if let Some(rules_path) = matches.get_one::<String>("declare-check") {
+        let rules = declare::load_rules(rules_path).expect("Failed to load DECLARE rules");
+        println!("{}", declare::check(&traces::build_traces(cases).expect("Failed to build the traces"), &rules));
+        std::process::exit(0);
+    }
+    if let Some(open_path) = matches.get_one::<String>("predict") {
+        let open_cases = preprocess_events(csv_parser::parse_csv_in(open_path, &source_zone).expect("Failed to parse CSV with open cases"));
+        let predictor = prediction::Predictor::train(&traces::build_traces(cases).expect("Failed to build the traces"), *matches.get_one::<usize>("knn").unwrap());
+        println!("{}", predictor.predict(&traces::build_traces(open_cases).expect("Failed to build the traces"), display_zone));
+        std::process::exit(0);
+    }
+    if let Some(abstraction) = matches.get_one::<transition_system::Abstraction>("transition-system") {
+        let system = transition_system::TransitionSystem::build(&traces::build_traces(cases).expect("Failed to build the traces"), *abstraction);
+        if let Some(dot_path) = matches.get_one::<String>("dot") {
+            std::fs::write(dot_path, system.to_dot()).expect("Failed to write DOT file");
+        }
+        println!("{}", system.to_json());
+        std::process::exit(0);
+    }
+    if let Some(&top) = matches.get_one::<usize>("anomalies") {
+        let traces = traces::build_traces(cases).expect("Failed to build the traces");
+        println!("{}", anomalies::to_json(&anomalies::score(&traces), top, display_zone));
+        std::process::exit(0);
+    }
+    if matches.get_flag("patterns") {
+        let miner = patterns::Miner {
+            min_support: *matches.get_one::<f64>("pattern-support").unwrap(),
+            max_gap: matches.get_one::<usize>("max-gap").copied(),
+            min_length: *matches.get_one::<usize>("min-length").unwrap(),
+            max_length: *matches.get_one::<usize>("max-length").unwrap(),
+        };
+        let variants = if matches.get_flag("gold") { sequential::process_cases(cases) } else { parallel::process_cases(cases) }.expect("Failed to compute the variants");
+        let mined = miner.mine(&variants);
+        println!("{}", patterns::to_json(&mined, *matches.get_one::<usize>("top").unwrap(), matches.get_flag("with-names")));
+        std::process::exit(0);
+    }
+    if let Some(attribute) = matches.get_one::<String>("group-by") {
+        if let Some(table_path) = matches.get_one::<String>("case-table") {
+            breakdown::join(&mut case_attributes, breakdown::read_case_table(table_path).expect("Failed to read the case table"));
+        }
+        let bands = match matches.get_one::<String>("bands") {
+            Some(bands) => breakdown::parse_bands(bands).expect("Failed to parse the value bands"),
+            None => Vec::new(),
+        };
+        let variants = if matches.get_flag("gold") {
+            sequential::process_cases_with_case_ids(cases)
+        } else {
+            parallel::process_cases_with_case_ids(cases)
+        }.expect("Failed to compute the variants");
+        let breakdown = breakdown::Breakdown::compute(&variants, &case_attributes, attribute, &bands);
+        println!("{}", breakdown.to_json(*matches.get_one::<usize>("top").unwrap(), matches.get_flag("with-names")));
+        std::process::exit(0);
+    }
+    // Decimate input data if requested (only used for integration tests)
+    if let Some(factor) = matches.get_one::<usize>("decimate") {
+        if !matches.get_flag("no-time-eval") {
+            println!("Decimate the input data by factor: {}", factor);
+        }
+        cases = decimate_vec(cases, *factor);
+        if let Some(log) = encoded_log.as_mut() {
+            log.events = decimate_vec(std::mem::take(&mut log.events), *factor);
+        }
+    }
+    let events = encoded_log.as_ref().map(|log| log.events.len()).unwrap_or(cases.len());
+    // The report needs the traces (timestamps) besides the variants
+    let report_traces = matches.contains_id("report").then(|| traces::build_traces(cases.clone()).expect("Failed to build the traces"));
+    // (outside of the measured time)
+    let kept_cases = keep_cases.then(|| cases.clone());
+    let begin = Instant::now();
+    // Add the call to your solution here
+
+    // Run the solution
+    let mut variant_cases = None;
+    let top_variants = if matches.get_flag("out-of-core") {
+        let spill_dir = matches.get_one::<String>("spill-dir").map(std::path::PathBuf::from).unwrap_or_else(std::env::temp_dir);
+        let partitions = *matches.get_one::<usize>("partitions").unwrap();
+        out_of_core::process_file(file_path, partitions, &spill_dir, &source_zone).expect("Failed to process the log out of core")
+    } else if let Some(state_path) = matches.get_one::<String>("state") {
+        let end_activity = matches.get_one::<String>("end-activity").unwrap();
+        let end_activity = activities::try_str_to_num(end_activity).unwrap_or_else(|| panic!("Unknown end activity: {}", end_activity));
+        let mut state = incremental::State::load_or_new(state_path, end_activity).expect("Failed to load state");
+        let update = state.update(cases).expect("Failed to update state");
+        state.save(state_path).expect("Failed to save state");
+        // Reports what the batch changed (including ignored late events)
+        eprintln!("{}", update.to_json());
+        state.top_variants()
+    } else if with_case_ids {
+        let variants = if matches.get_flag("gold") {
+            sequential::process_cases_with_case_ids(cases)
+        } else {
+            parallel::process_cases_with_case_ids(cases)
+        }.expect("Failed to compute the variants");
+        let counts = assignments::counts(&variants);
+        variant_cases = Some(variants);
+        counts
+    } else if matches.get_flag("gold") {   // Uses the golden sample
+        sequential::process_cases(cases).expect("Failed to compute the variants")
+    } else if matches.get_flag("interned") {
+        interned::process_cases(cases).expect("Failed to compute the variants")
+    } else if let Some(log) = encoded_log {
+        parallel::process_encoded(log.events)
+    } else {
+        parallel::process_cases(cases).expect("Failed to compute the variants")
+    };
+
+    let top_n = top_variants.iter().take(*matches.get_one::<usize>("top").unwrap()).collect::<Vec<_>>();
+
+    // Prepare JSON output
+    let mut json_output = json!(top_n);
+
+    if matches.get_flag("with-names") {
+        let top_n = activities::convert_variants_to_strings(top_n.clone());
+        json_output = json!(top_n);
+    }
+
+    let end = Instant::now();
+
+    let duration = end.duration_since(begin);
+    if !matches.get_flag("no-time-eval") {
+        println!("Duration: {} milliseconds", duration.as_millis());
+    }
+    println!("{}", json_output);
+
+    let case_assignments = kept_cases
+        .map(|cases| traces::assign_variants(&traces::build_traces(cases).expect("Failed to build the traces"), &top_variants));
+    if let (Some(db_path), Some(case_assignments)) = (matches.get_one::<String>("persist"), &case_assignments) {
+        let run = sqlite::Run {
+            source: file_path,
+            // (the case ids of --assignments come from the parallel engine, also with --interned)
+            engine: if matches.get_flag("gold") {
+                "gold"
+            } else if matches.get_flag("interned") && !with_case_ids {
+                "interned"
+            } else {
+                "parallel"
+            },
+            events,
+            duration_ms: duration.as_millis(),
+        };
+        sqlite::persist(db_path, &run, &top_variants, case_assignments).expect("Failed to persist results");
+    }
+    if let Some(variants_path) = matches.get_one::<String>("parquet-variants") {
+        columnar::write_variants(variants_path, &top_variants).expect("Failed to write Parquet variants");
+    }
+    if let (Some(cases_path), Some(case_assignments)) = (matches.get_one::<String>("parquet-cases"), &case_assignments) {
+        columnar::write_cases(cases_path, case_assignments).expect("Failed to write Parquet cases");
+    }
+    if let (Some(report_path), Some(report_traces)) = (matches.get_one::<String>("report"), &report_traces) {
+        report::write(report_path, file_path, events, report_traces, &top_variants, display_zone).expect("Failed to write the report");
+    }
+    if let (Some(assignments_path), Some(variant_cases)) = (matches.get_one::<String>("assignments"), &variant_cases) {
+        assignments::write(assignments_path, variant_cases).expect("Failed to write assignments");
+    }
+}
+
+fn command() -> Command {
+    Command::new("ProcureToPay")
         .version("0.1.0")
         .about("Crunches cases from Procure to Pay to find most frequent variants of activities")
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        // Lets the given arguments override those of the configuration profile
+        .args_override_self(true)
+        .subcommand(generate_command())
+        .subcommand(simulate_command())
+        .subcommand(stream_command())
+        .subcommand(serve_command())
+        .subcommand(
+            Command::new("explore")
+                .about("Explores the variants of the log interactively in the terminal")
+                .arg(Arg::new("file").help("Path to the CSV file with cases").required(true).index(1))
+                .arg(zone_arg("source-tz", "Time zone of the timestamps without an offset (and of the times of the filters)"))
+                .arg(zone_arg("display-tz", "Time zone the times are shown in (default: --source-tz)")),
+        )
         .arg(
             Arg::new("file")
                 .help("Path to the CSV file with cases")
-                .required(true)
+                .required_unless_present("config")
                 .index(1),
         )
+        .arg(zone_arg("source-tz", "Time zone of the timestamps without an offset (e.g. Europe/Berlin or +02:00, default UTC)"))
+        .arg(zone_arg("display-tz", "Time zone the times are shown in (default: --source-tz)"))
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .help("TOML configuration file with analysis profiles (input, activities, dedup, filters, engine, outputs)")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .help("Profile of the configuration file (defaults to its `profile` key)")
+                .value_name("NAME")
+                .requires("config"),
+        )
+        .arg(
+            Arg::new("top")
+                .long("top")
+                .help("Number of printed variants")
+                .default_value("10")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("rename")
+                .long("rename")
+                .help("Renames the activity label of the log before the analysis, e.g. \"Post invoice in FI=Post invoice\"")
+                .value_name("LABEL=ACTIVITY")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("mapping")
+                .long("mapping")
+                .help("TOML file mapping the activity labels of the log: renames/merges ([rename]) and categories ([categories])")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("level")
+                .long("level")
+                .help("Level of detail of the activities: the renamed labels (fine), or their categories of --mapping (coarse)")
+                .value_parser(["fine", "coarse"])
+                .default_value("fine"),
+        )
+        .arg(
+            Arg::new("dedup")
+                .long("dedup")
+                .help("Repeated activities of a case: consecutive ones count once (consecutive), or only the first one is kept (first-occurrence)")
+                .value_parser(["consecutive", "first-occurrence"])
+                .default_value("consecutive"),
+        )
+        .arg(
+            Arg::new("contains")
+                .long("contains")
+                .help("Keeps only the cases containing the activity")
+                .value_name("ACTIVITY")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("excludes")
+                .long("excludes")
+                .help("Drops the cases containing the activity")
+                .value_name("ACTIVITY")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("from")
+                .long("from")
+                .help("Keeps only the cases starting at or after the time (YYYY-MM-DD[ HH:MM:SS])")
+                .value_name("TIME"),
+        )
+        .arg(
+            Arg::new("to")
+                .long("to")
+                .help("Keeps only the cases starting before the time (YYYY-MM-DD[ HH:MM:SS])")
+                .value_name("TIME"),
+        )
         .arg(
             Arg::new("crunch-activities")
                 .long("crunch-activities")
@@ -44,6 +456,13 @@ fn main() {
                 .help("Uses original golden processing engine (not optimized)")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("interned")
+                .long("interned")
+                .help("Uses the allocation-free engine on interned ids")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("gold"),
+        )
         .arg(
             Arg::new("no-time-eval")
                 .long("no-time-eval")
@@ -57,64 +476,617 @@ fn main() {
                 .value_name("FACTOR")
                 .value_parser(clap::value_parser!(usize)),
         )
-        .get_matches();
-    let file_path = matches.get_one::<String>("file").expect("File name is required");
+        .arg(
+            Arg::new("declare-mine")
+                .long("declare-mine")
+                .help("Discovers DECLARE constraints from the log (see --min-support, --min-confidence)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("min-support")
+                .long("min-support")
+                .help("Minimum support of discovered DECLARE constraints")
+                .value_name("RATIO")
+                .default_value("0.8")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            Arg::new("min-confidence")
+                .long("min-confidence")
+                .help("Minimum confidence of discovered DECLARE constraints")
+                .value_name("RATIO")
+                .default_value("0.9")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            Arg::new("declare-check")
+                .long("declare-check")
+                .help("Checks DECLARE rules from the file per case and lists violating cases and variants")
+                .value_name("RULES"),
+        )
+        .arg(
+            Arg::new("resources")
+                .long("resources")
+                .help("Mines handovers, activity-resource matrix, workload and segregation of duties from a resource column of the CSV")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["sqlite", "parquet"]),
+        )
+        .arg(
+            Arg::new("resource-column")
+                .long("resource-column")
+                .help("Name of the CSV column with the resource (e.g. user or department)")
+                .value_name("COLUMN")
+                .default_value("user"),
+        )
+        .arg(
+            Arg::new("sod")
+                .long("sod")
+                .help("Segregation-of-duties rule: two activities that must not be performed by the same resource in a case")
+                .value_name("A|B")
+                .default_value("Create purchase order item|Clear open item")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("dot")
+                .long("dot")
+                .help("Writes the handover-of-work network (see --resources) or the transition system (see --transition-system) in DOT format to the file")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("transition-system")
+                .long("transition-system")
+                .help("Builds the transition system of the traces with the state abstraction (prefix, set, multiset or last:K), annotated with visits and elapsed/remaining times")
+                .value_name("ABSTRACTION")
+                .value_parser(|s: &str| transition_system::Abstraction::parse(s).map_err(|e| e.to_string()))
+                .conflicts_with_all(["interned", "state", "persist", "parquet-cases", "assignments", "report", "group-by", "patterns", "anomalies"]),
+        )
+        .arg(
+            Arg::new("anomalies")
+                .long("anomalies")
+                .help("Scores how unusual the cases are (rare variant and transitions, extreme throughput time, repeated activities), prints the top K with explanations and traces")
+                .value_name("K")
+                .value_parser(clap::value_parser!(usize))
+                .conflicts_with_all(["interned", "state", "persist", "parquet-cases", "assignments", "report", "group-by", "patterns"]),
+        )
+        .arg(
+            Arg::new("patterns")
+                .long("patterns")
+                .help("Mines frequent sequential patterns (PrefixSpan), ranked by the number of variants they occur in")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["interned", "state", "persist", "parquet-cases", "assignments", "report", "group-by"]),
+        )
+        .arg(
+            Arg::new("pattern-support")
+                .long("pattern-support")
+                .help("Minimum share of the cases with a pattern of --patterns")
+                .value_name("RATIO")
+                .default_value("0.05")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            Arg::new("max-gap")
+                .long("max-gap")
+                .help("Maximum number of other activities between two activities of a pattern (default: any)")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("min-length")
+                .long("min-length")
+                .help("Minimum number of activities of a pattern")
+                .default_value("2")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("max-length")
+                .long("max-length")
+                .help("Maximum number of activities of a pattern")
+                .default_value("5")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("group-by")
+                .long("group-by")
+                .help("Breaks the variants down by a case attribute (of the CSV's extra columns or --case-table): top variants per value and how differently the groups behave")
+                .value_name("ATTRIBUTE")
+                .conflicts_with_all(["interned", "state", "persist", "parquet-cases", "assignments", "report"]),
+        )
+        .arg(
+            Arg::new("case-table")
+                .long("case-table")
+                .help("CSV file with case attributes (case id in the first column) joined to those of the log's extra columns")
+                .value_name("FILE")
+                .requires("group-by"),
+        )
+        .arg(
+            Arg::new("bands")
+                .long("bands")
+                .help("Groups a numeric attribute of --group-by into value bands by their ascending upper bounds")
+                .value_name("BOUNDS")
+                .requires("group-by"),
+        )
+        .arg(
+            Arg::new("predict")
+                .long("predict")
+                .help("Trains on the (completed) cases and predicts remaining time and next activity of open cases from the file")
+                .value_name("OPEN_CASES"),
+        )
+        .arg(
+            Arg::new("knn")
+                .long("knn")
+                .help("Number of nearest prefixes used by k-NN prediction")
+                .value_name("K")
+                .default_value("5")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("sqlite")
+                .long("sqlite")
+                .help("Reads events from the SQLite database (the file argument) using --sqlite-query")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("sqlite-query")
+                .long("sqlite-query")
+                .help("SQL query returning case_id, activity and timestamp columns")
+                .value_name("SQL")
+                .default_value(sqlite::DEFAULT_QUERY),
+        )
+        .arg(
+            Arg::new("persist")
+                .long("persist")
+                .help("Writes run metadata, variants and case-to-variant assignments into the SQLite database")
+                .value_name("DB"),
+        )
+        .arg(
+            Arg::new("parquet")
+                .long("parquet")
+                .help("Reads events from the Parquet file (see --case-column, --activity-column, --timestamp-column)")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("sqlite"),
+        )
+        .arg(
+            Arg::new("case-column")
+                .long("case-column")
+                .help("Name of the case id column in Parquet input")
+                .value_name("COLUMN")
+                .default_value("case_id"),
+        )
+        .arg(
+            Arg::new("activity-column")
+                .long("activity-column")
+                .help("Name of the activity column in Parquet input")
+                .value_name("COLUMN")
+                .default_value("activity"),
+        )
+        .arg(
+            Arg::new("timestamp-column")
+                .long("timestamp-column")
+                .help("Name of the timestamp column in Parquet input")
+                .value_name("COLUMN")
+                .default_value("timestamp"),
+        )
+        .arg(
+            Arg::new("parquet-variants")
+                .long("parquet-variants")
+                .help("Writes all variants (variant_id, cases, activities) into the Parquet file")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("parquet-cases")
+                .long("parquet-cases")
+                .help("Writes case-to-variant assignments (case_id, variant_id) into the Parquet file")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("assignments")
+                .long("assignments")
+                .help("Writes case-to-variant assignments into the file (.csv: case_id;variant_id, .json: variants with their case ids)")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("report")
+                .long("report")
+                .help("Writes a self-contained HTML report (log statistics, variants, directly-follows graph, throughput times) into the file")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("state")
+                .long("state")
+                .help("Incremental mode: adds the events to the engine state in the file (created if missing) and prints the variants of finalized cases")
+                .value_name("FILE")
+                .conflicts_with_all(["gold", "persist", "parquet-cases", "assignments", "report"]),
+        )
+        .arg(
+            Arg::new("end-activity")
+                .long("end-activity")
+                .help("Activity which finalizes a case in the incremental mode")
+                .value_name("ACTIVITY")
+                .default_value("Clear open item"),
+        )
+        .arg(
+            Arg::new("out-of-core")
+                .long("out-of-core")
+                .help("Partitions the CSV by case into spill files and processes one partition at a time (for logs larger than memory)")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "sqlite", "parquet", "gold", "decimate", "state", "persist", "parquet-cases", "assignments",
+                    "crunch-activities", "declare-mine", "declare-check", "predict", "resources", "report", "group-by",
+                    "patterns", "anomalies", "transition-system", "rename", "mapping", "level", "dedup", "contains", "excludes", "from", "to",
+                ]),
+        )
+        .arg(
+            Arg::new("partitions")
+                .long("partitions")
+                .help("Number of partitions of --out-of-core (each one has to fit into memory)")
+                .default_value("64")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("spill-dir")
+                .long("spill-dir")
+                .help("Directory for the partitions of --out-of-core (defaults to the system temp directory)")
+                .value_name("DIR"),
+        )
+}
 
-    // Read and parse the CSV
-    let mut cases = csv_parser::parse_csv(file_path).expect("Failed to parse CSV");
+fn generate_command() -> Command {
+    Command::new("generate")
+        .about("Generates a synthetic event log from a variant distribution or a Petri net")
+        .arg(
+            Arg::new("variants")
+                .long("variants")
+                .help("JSON file with variants and their counts (as printed with --with-names)")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("petri-net")
+                .long("petri-net")
+                .help("JSON file with a Petri net (transitions with label, inputs, outputs; initial and final marking)")
+                .value_name("FILE"),
+        )
+        .group(ArgGroup::new("model").args(["variants", "petri-net"]).required(true))
+        .arg(
+            Arg::new("cases")
+                .long("cases")
+                .help("Number of cases to generate")
+                .default_value("1000")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .help("Seed of the random generator (the same seed yields the same log)")
+                .default_value("42")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("start")
+                .long("start")
+                .help("Arrival of the first case")
+                .default_value("2024-01-01 00:00:00")
+                .value_parser(|s: &str| chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")),
+        )
+        .arg(
+            Arg::new("arrival")
+                .long("arrival")
+                .help("Distribution of time between case arrivals in seconds (fixed:S, uniform:MIN:MAX, exp:MEAN, normal:MEAN:SD, lognormal:MU:SIGMA)")
+                .default_value("exp:3600")
+                .value_parser(|s: &str| generator::Distribution::parse(s).map_err(|e| e.to_string())),
+        )
+        .arg(
+            Arg::new("default-duration")
+                .long("default-duration")
+                .help("Distribution of time before each activity in seconds (see --arrival)")
+                .default_value("exp:86400")
+                .value_parser(|s: &str| generator::Distribution::parse(s).map_err(|e| e.to_string())),
+        )
+        .arg(
+            Arg::new("duration")
+                .long("duration")
+                .help("Distribution of time before the activity, e.g. \"Post invoice in MM=exp:7200\"")
+                .value_name("ACTIVITY=DISTRIBUTION")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("swap")
+                .long("swap")
+                .help("Probability of swapping an event with the previous one in the case")
+                .default_value("0")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            Arg::new("missing")
+                .long("missing")
+                .help("Probability of dropping an event")
+                .default_value("0")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            Arg::new("duplicate")
+                .long("duplicate")
+                .help("Probability of duplicating an event")
+                .default_value("0")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            Arg::new("shuffle")
+                .long("shuffle")
+                .help("Shuffles the events (otherwise ordered by case)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .help("Output CSV file (stdout if not set)")
+                .value_name("FILE"),
+        )
+}
 
-    if matches.get_flag("crunch-activities") {
-        activities::crunch_activities(cases);
-        std::process::exit(0);
-    }
-    // Decimate input data if requested (only used for integration tests)
-    if let Some(factor) = matches.get_one::<usize>("decimate") {
-        if !matches.get_flag("no-time-eval") {
-            println!("Decimate the input data by factor: {}", factor);
-        }
-        cases = decimate_vec(cases, *factor);
-    }
-    let begin = Instant::now();
-    // Add the call to your solution here
+fn generate(matches: &ArgMatches) {
+    let model = match matches.get_one::<String>("variants") {
+        Some(path) => generator::load_variants(path),
+        None => generator::load_petri_net(matches.get_one::<String>("petri-net").unwrap()),
+    }.expect("Failed to load the model");
 
-    // Run the solution
-    let top_variants;
-    if matches.get_flag("gold") {   // Uses the golden sample
-        top_variants = sequential::process_cases(cases);
-    } else {
-        top_variants = parallel::process_cases(cases);
-    }
+    let durations = matches
+        .get_many::<String>("duration")
+        .unwrap_or_default()
+        .map(|spec| {
+            let (activity, distribution) = spec.rsplit_once('=').expect("Duration must be ACTIVITY=DISTRIBUTION");
+            let activity = generator::check_activity(activity).expect("Invalid duration activity");
+            (activity, generator::Distribution::parse(distribution).expect("Invalid duration distribution"))
+        })
+        .collect();
+    let config = generator::Config {
+        cases: *matches.get_one::<usize>("cases").unwrap(),
+        seed: *matches.get_one::<u64>("seed").unwrap(),
+        start: *matches.get_one::<chrono::NaiveDateTime>("start").unwrap(),
+        arrival: matches.get_one::<generator::Distribution>("arrival").unwrap().clone(),
+        durations,
+        default_duration: matches.get_one::<generator::Distribution>("default-duration").unwrap().clone(),
+        noise: generator::Noise {
+            swap: *matches.get_one::<f64>("swap").unwrap(),
+            missing: *matches.get_one::<f64>("missing").unwrap(),
+            duplicate: *matches.get_one::<f64>("duplicate").unwrap(),
+        },
+        shuffle: matches.get_flag("shuffle"),
+    };
 
-    let top_10 = top_variants.iter().take(10).collect::<Vec<_>>();
+    let events = generator::generate(&model, &config).expect("Failed to generate the log");
+    let result = match matches.get_one::<String>("output") {
+        Some(path) => std::fs::File::create(path)
+            .and_then(|file| generator::write_csv(&events, &mut std::io::BufWriter::new(file))),
+        None => generator::write_csv(&events, &mut std::io::BufWriter::new(std::io::stdout().lock())),
+    };
+    result.expect("Failed to write the generated log");
+}
 
-    // Prepare JSON output
-    let mut json_output = json!(top_10);
+fn simulate_command() -> Command {
+    Command::new("simulate")
+        .about("Simulates the discovered process with what-if overrides and compares it with the as-is log")
+        .arg(
+            Arg::new("file")
+                .help("Path to the CSV file with cases")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::new("duration-factor")
+                .long("duration-factor")
+                .help("Multiplies durations before the activity, e.g. \"Post invoice in MM=0.5\"")
+                .value_name("ACTIVITY=FACTOR")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("routing-factor")
+                .long("routing-factor")
+                .help("Multiplies the probability of routing to the activity, e.g. \"Set payment block=0.8\"")
+                .value_name("ACTIVITY=FACTOR")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("history")
+                .long("history")
+                .help("Number of previous activities the routing probabilities depend on")
+                .default_value("2")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("cases")
+                .long("cases")
+                .help("Number of simulated cases (defaults to the number of cases in the log)")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .help("Seed of the random generator")
+                .default_value("42")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("top")
+                .long("top")
+                .help("Number of variants reported per scenario")
+                .default_value("10")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(zone_arg("source-tz", "Time zone of the timestamps without an offset"))
+}
 
-    if matches.get_flag("with-names") {
-        let top_10 = convert_variants_to_strings(top_10.clone());
-        json_output = json!(top_10);
-    }
+fn simulate(matches: &ArgMatches) {
+    let file_path = matches.get_one::<String>("file").unwrap();
+    let zone = matches.get_one::<timezones::Zone>("source-tz").copied().unwrap_or_default();
+    let events = csv_parser::parse_csv_in(file_path, &zone).expect("Failed to parse CSV");
+    let traces = traces::build_traces(events).expect("Failed to build the traces");
 
-    let end = Instant::now();
+    let parse_overrides = |name: &str| {
+        matches
+            .get_many::<String>(name)
+            .unwrap_or_default()
+            .map(|spec| simulation::parse_override(spec).expect("Invalid override"))
+            .collect()
+    };
+    let overrides = simulation::Overrides {
+        duration_factors: parse_overrides("duration-factor"),
+        routing_factors: parse_overrides("routing-factor"),
+    };
+    let cases = matches.get_one::<usize>("cases").copied().unwrap_or(traces.len());
+    let seed = *matches.get_one::<u64>("seed").unwrap();
+    let top = *matches.get_one::<usize>("top").unwrap();
+    let history = *matches.get_one::<usize>("history").unwrap();
+    println!("{}", simulation::what_if(&traces, history, &overrides, cases, seed, top));
+}
 
-    let duration = end.duration_since(begin);
-    if !matches.get_flag("no-time-eval") {
-        println!("Duration: {} milliseconds", duration.as_millis());
-    }
-    println!("{}", json_output.to_string());
+fn stream_command() -> Command {
+    Command::new("stream")
+        .about("Monitors variants of newline-delimited events from stdin or a local socket")
+        .arg(
+            Arg::new("listen")
+                .long("listen")
+                .help("Listens on the local TCP address (e.g. 127.0.0.1:7878) instead of reading stdin")
+                .value_name("ADDRESS"),
+        )
+        .arg(
+            Arg::new("unix-socket")
+                .long("unix-socket")
+                .help("Listens on the Unix socket instead of reading stdin")
+                .value_name("PATH")
+                .conflicts_with("listen"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Format of the lines: csv (case_id;activity;timestamp) or json ({\"case_id\", \"activity\", \"timestamp\"})")
+                .value_parser(["csv", "json"])
+                .default_value("csv"),
+        )
+        .arg(
+            Arg::new("interval")
+                .long("interval")
+                .help("Seconds between two snapshots")
+                .default_value("10")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("top")
+                .long("top")
+                .help("Number of variants in each snapshot")
+                .default_value("10")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("case-timeout")
+                .long("case-timeout")
+                .help("Finalizes cases with no event for this many seconds (of event time)")
+                .value_parser(clap::value_parser!(i64)),
+        )
+        .arg(
+            Arg::new("end-activity")
+                .long("end-activity")
+                .help("Activity which finalizes a case")
+                .value_name("ACTIVITY")
+                .default_value("Clear open item"),
+        )
+        .arg(
+            Arg::new("state")
+                .long("state")
+                .help("Starts from the engine state in the file (see --state) and saves it with every snapshot")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("with-names")
+                .long("with-names")
+                .help("Uses activity names (instead of numbers) in the snapshots")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(zone_arg("source-tz", "Time zone of the timestamps without an offset"))
+        .arg(zone_arg("display-tz", "Time zone the watermark is shown in (default: --source-tz)"))
 }
 
-fn convert_variants_to_strings(variants: Vec<&(Vec<u8>, usize)>) -> Vec<(Vec<String>, usize)> {
-    variants
-        .into_iter()
-        .map(|(activity_numbers, count)| {
-            let activity_names = activity_numbers
-                .iter()
-                .map(|&num| activities::num_to_str(num).to_string()) // Convert &str to String
-                .collect::<Vec<String>>();
-            (activity_names, *count)
-        })
-        .collect()
+fn run_stream(matches: &ArgMatches) {
+    let end_activity = matches.get_one::<String>("end-activity").unwrap();
+    let end_activity = activities::try_str_to_num(end_activity).unwrap_or_else(|| panic!("Unknown end activity: {}", end_activity));
+    let state_path = matches.get_one::<String>("state");
+    let state = match state_path {
+        Some(state_path) => incremental::State::load_or_new(state_path, end_activity).expect("Failed to load state"),
+        None => incremental::State::new(end_activity),
+    };
+    let config = stream::Config {
+        format: if matches.get_one::<String>("format").unwrap() == "json" { stream::Format::Json } else { stream::Format::Csv },
+        top: *matches.get_one::<usize>("top").unwrap(),
+        with_names: matches.get_flag("with-names"),
+        case_timeout: matches.get_one::<i64>("case-timeout").map(|seconds| chrono::Duration::seconds(*seconds)),
+        zone: matches.get_one::<timezones::Zone>("source-tz").copied().unwrap_or_default(),
+        display_zone: matches.get_one::<timezones::Zone>("display-tz").or(matches.get_one::<timezones::Zone>("source-tz")).copied(),
+    };
+    let source = if let Some(address) = matches.get_one::<String>("listen") {
+        stream::Source::Tcp(address.clone())
+    } else if let Some(path) = matches.get_one::<String>("unix-socket") {
+        #[cfg(unix)]
+        { stream::Source::Unix(path.clone()) }
+        #[cfg(not(unix))]
+        panic!("Unix sockets are not supported on this platform: {}", path)
+    } else {
+        stream::Source::Reader(Box::new(std::io::BufReader::new(std::io::stdin())))
+    };
+
+    let mut monitor = stream::Monitor::new(state, config);
+    let interval = std::time::Duration::from_secs(*matches.get_one::<u64>("interval").unwrap());
+    let save_state = |monitor: &stream::Monitor| match state_path {
+        Some(state_path) => monitor.save_state(state_path),
+        None => Ok(()),
+    };
+    stream::run(source, &mut monitor, interval, &mut std::io::stdout(), save_state).expect("Failed to process the stream");
+}
+
+fn zone_arg(name: &'static str, help: &'static str) -> Arg {
+    Arg::new(name)
+        .long(name)
+        .help(help)
+        .value_name("ZONE")
+        .value_parser(|zone: &str| timezones::Zone::parse(zone).map_err(|err| err.to_string()))
+}
+
+fn serve_command() -> Command {
+    Command::new("serve")
+        .about("Serves a local HTTP API with variants, directly-follows graph and statistics of cached logs")
+        .arg(
+            Arg::new("logs")
+                .help("CSV files cached at the start (as logs named by their file names)")
+                .num_args(0..)
+                .index(1),
+        )
+        .arg(
+            Arg::new("listen")
+                .long("listen")
+                .help("Local address to listen on")
+                .value_name("ADDRESS")
+                .default_value("127.0.0.1:8080"),
+        )
+        .arg(
+            Arg::new("data-dir")
+                .long("data-dir")
+                .help("Directory the logs can be loaded from by path (POST /logs?name=...&path=...)")
+                .value_name("DIR")
+                .default_value("."),
+        )
+        .arg(zone_arg("source-tz", "Time zone of the timestamps without an offset in the logs given at the start"))
+}
+
+fn serve(matches: &ArgMatches) {
+    let mut api = server::Api::new(std::path::Path::new(matches.get_one::<String>("data-dir").unwrap()));
+    let zone = matches.get_one::<timezones::Zone>("source-tz").copied();
+    for file_path in matches.get_many::<String>("logs").unwrap_or_default() {
+        let name = std::path::Path::new(file_path).file_name().and_then(|name| name.to_str()).unwrap_or(file_path);
+        let events = csv_parser::parse_csv_in(file_path, &zone.unwrap_or_default()).expect("Failed to parse CSV");
+        api.add_log(name, events, zone).expect("Failed to cache the log");
+    }
+    let address = matches.get_one::<String>("listen").unwrap();
+    let http = server::bind(address).expect("Failed to start the server");
+    eprintln!("Listening on http://{}", address);
+    server::run(&http, &mut api).expect("Failed to serve");
 }
 
 fn decimate_vec<T>(vec: Vec<T>, n: usize) -> Vec<T> {
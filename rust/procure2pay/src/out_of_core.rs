@@ -0,0 +1,86 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  out_of_core: Variant counting for logs larger than memory
+//          * streams the CSV once and hash-partitions its lines by case_id into spill files
+//            (all events of a case end up in the same partition)
+//          * processes one partition at a time with the parallel engine
+//          * merges the counts (partitions share no cases, so the counts simply add up)
+//
+use crate::csv_parser;
+use crate::parallel;
+use crate::timezones::Zone;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// Sorted variants with their counts (as the engines return them)
+type Variants = Vec<(Vec<u8>, usize)>;
+
+// Directory with the spill files, removed with all partitions when dropped
+struct SpillDir(PathBuf);
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn partition_of(case_id: &str, partitions: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    case_id.hash(&mut hasher);
+    (hasher.finish() % partitions as u64) as usize
+}
+
+// Splits the CSV into partitions (CSV files with the same header), returns their paths
+fn spill(file_path: &str, partitions: usize, spill_dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut lines = BufReader::new(File::open(file_path)?).lines();
+    let header = match lines.next() {
+        Some(header) => header?,
+        None => return Ok(Vec::new()),
+    };
+
+    let paths: Vec<PathBuf> = (0..partitions).map(|i| spill_dir.join(format!("partition-{}.csv", i))).collect();
+    let mut writers = Vec::with_capacity(partitions);
+    for path in &paths {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{}", header)?;
+        writers.push(writer);
+    }
+    for line in lines {
+        let line = line?;
+        let case_id = line.split(';').next().unwrap_or_default();
+        writeln!(writers[partition_of(case_id, partitions)], "{}", line)?;
+    }
+    for mut writer in writers {
+        writer.flush()?;
+    }
+    Ok(paths)
+}
+
+// The same result as parallel::process_cases(csv_parser::parse_csv_in(file_path, zone)), but only one
+// partition of the events is in memory at a time
+pub fn process_file(file_path: &str, partitions: usize, spill_dir: &Path, zone: &Zone) -> Result<Variants, Box<dyn Error>> {
+    let spill_dir = SpillDir(spill_dir.join(format!("procure2pay-spill-{}", std::process::id())));
+    fs::create_dir_all(&spill_dir.0)?;
+
+    let mut variant_count: HashMap<Vec<u8>, usize> = HashMap::new();
+    for path in spill(file_path, partitions.max(1), &spill_dir.0)? {
+        let cases = csv_parser::parse_csv_in(path.to_str().ok_or("Invalid spill path")?, zone)?;
+        // The partition is not needed anymore (frees the disk space early)
+        fs::remove_file(&path)?;
+        for (variant, count) in parallel::process_cases(cases)? {
+            *variant_count.entry(variant).or_insert(0) += count;
+        }
+    }
+
+    let mut sorted_variants: Vec<_> = variant_count.into_iter().collect();
+    sorted_variants.sort_by_key(|v| std::cmp::Reverse(v.1));
+
+    Ok(sorted_variants)
+}
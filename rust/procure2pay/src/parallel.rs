@@ -9,13 +9,21 @@
 use chrono::NaiveDateTime;
 use rayon::prelude::*;
 use std::collections::HashMap;
+use std::error::Error;
 use crate::activities;
+use crate::assignments::VariantCases;
 
-pub fn process_cases(raw_cases: Vec<(String, NaiveDateTime, String)>) -> Vec<(Vec<u8>, usize)>  {
+// Variants with their number of cases, the most frequent first
+type Variants = Vec<(Vec<u8>, usize)>;
+
+// An already encoded event: (case index, timestamp, activity number)
+pub type EncodedEvent = (u32, NaiveDateTime, u8);
+
+pub fn process_cases(raw_cases: Vec<(String, NaiveDateTime, String)>) -> Result<Variants, Box<dyn Error>>  {
     let mut cases = raw_cases;
     // Handles special cases
-    if cases.len() == 0 {
-        return Vec::new();
+    if cases.is_empty() {
+        return Ok(Vec::new());
     }
 
     // Needs to sort by
@@ -24,37 +32,9 @@ pub fn process_cases(raw_cases: Vec<(String, NaiveDateTime, String)>) -> Vec<(Ve
     // * by activity too (see the explanation in sequential.rs:26-29) due to duplications/auto-transitions
     cases.par_sort_unstable_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)).then_with(|| a.2.cmp(&b.2)));
 
-    let mut num_threads = rayon::current_num_threads().max(1);
-    // Considers some "sane" value to split the work between workers
-    if cases.len() < 256 {
-        num_threads = 1;
-    }
-
-    // Splits the work per thead:
-    // * finds split points where case_id "just alters"
-    // * this way we process each subset separately with no overlapping case_id (so merging is trivial, and cheap)
-    let chunk_size = cases.len() / num_threads;
-    let mut splits = Vec::with_capacity(num_threads + 1);
-
-    for i in 0..num_threads {
-        let mut split_point = i * chunk_size;
-        if split_point > 0 {
-            let current_case_id = &cases[split_point].0;
-            let prev_case_id = &cases[split_point - 1].0;
-
-            while split_point < cases.len() && &cases[split_point].0 == prev_case_id {
-                split_point += 1;
-            }
-
-            while split_point > 0 && &cases[split_point].0 == current_case_id {
-                split_point -= 1;
-            }
-        }
-        splits.push(split_point);
-    }
-    splits.push(cases.len());
+    let splits = split_points(&cases, worker_threads(cases.len()), |a, b| a.0 == b.0);
 
-    // Processes each chunk in parallel
+    // Processes each chunk in parallel (the first unknown activity fails it)
     let partial_variants: Vec<HashMap<Vec<u8>, usize>> = splits
         .windows(2)
         .collect::<Vec<_>>()
@@ -73,8 +53,9 @@ pub fn process_cases(raw_cases: Vec<(String, NaiveDateTime, String)>) -> Vec<(Ve
                     current_case_id = case_id;
                     current_variant.clear();
                 }
-                if current_variant.last() != Some(&activities::str_to_num(activity_name)) {
-                    current_variant.push(activities::str_to_num(activity_name));
+                let activity = activities::try_str_to_num(activity_name).ok_or_else(|| unknown(activity_name))?;
+                if current_variant.last() != Some(&activity) {
+                    current_variant.push(activity);
                 }
             }
 
@@ -83,9 +64,9 @@ pub fn process_cases(raw_cases: Vec<(String, NaiveDateTime, String)>) -> Vec<(Ve
                 *variants.entry(current_variant).or_insert(0) += 1;
             }
 
-            variants
+            Ok(variants)
         })
-        .collect();
+        .collect::<Result<_, String>>()?;
 
     // Combines the results (a bit expensive as the variants overlap)
     let mut final_variants: HashMap<Vec<u8>, usize> = HashMap::new();
@@ -97,7 +78,132 @@ pub fn process_cases(raw_cases: Vec<(String, NaiveDateTime, String)>) -> Vec<(Ve
 
     // Collect and sort the variants by their count in descending order
     let mut sorted_variants: Vec<_> = final_variants.into_iter().collect();
-    sorted_variants.sort_by(|a, b| b.1.cmp(&a.1));
+    sorted_variants.sort_by_key(|v| std::cmp::Reverse(v.1));
+
+    Ok(sorted_variants)
+}
+
+// Same as process_cases(), but keeps the case ids of each variant (instead of their count)
+pub fn process_cases_with_case_ids(raw_cases: Vec<(String, NaiveDateTime, String)>) -> Result<VariantCases, Box<dyn Error>> {
+    let mut cases = raw_cases;
+    if cases.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    cases.par_sort_unstable_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)).then_with(|| a.2.cmp(&b.2)));
+    let splits = split_points(&cases, worker_threads(cases.len()), |a, b| a.0 == b.0);
+
+    let partial_variants: Vec<HashMap<Vec<u8>, Vec<String>>> = splits
+        .windows(2)
+        .collect::<Vec<_>>()
+        .par_iter()
+        .map(|window| {
+            let mut variants: HashMap<Vec<u8>, Vec<String>> = HashMap::new();
+            for case in cases[window[0]..window[1]].chunk_by(|a, b| a.0 == b.0) {
+                let mut variant: Vec<u8> = case
+                    .iter()
+                    .map(|event| activities::try_str_to_num(&event.2).ok_or_else(|| unknown(&event.2)))
+                    .collect::<Result<_, _>>()?;
+                variant.dedup();
+                variants.entry(variant).or_default().push(case[0].0.clone());
+            }
+            Ok(variants)
+        })
+        .collect::<Result<_, String>>()?;
+
+    // Chunks are ordered by case id, so the case ids of each variant stay in order
+    let mut final_variants: HashMap<Vec<u8>, Vec<String>> = HashMap::new();
+    for partial in partial_variants {
+        for (variant, case_ids) in partial {
+            final_variants.entry(variant).or_default().extend(case_ids);
+        }
+    }
+
+    let mut sorted_variants: Vec<_> = final_variants.into_iter().collect();
+    sorted_variants.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.1.cmp(&b.1)));
+
+    Ok(sorted_variants)
+}
+
+// Same as process_cases(), but on encoded events (e.g. from columnar input), so there are no
+// strings per event to sort, compare and convert
+pub fn process_encoded(raw_cases: Vec<EncodedEvent>) -> Vec<(Vec<u8>, usize)> {
+    let mut cases = raw_cases;
+    if cases.is_empty() {
+        return Vec::new();
+    }
+
+    // The same ordering as above (activities with the same timestamp ordered by their names)
+    cases.par_sort_unstable_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then_with(|| a.1.cmp(&b.1))
+            .then_with(|| activities::num_to_str(a.2).cmp(activities::num_to_str(b.2)))
+    });
+    let splits = split_points(&cases, worker_threads(cases.len()), |a, b| a.0 == b.0);
+
+    let partial_variants: Vec<HashMap<Vec<u8>, usize>> = splits
+        .windows(2)
+        .collect::<Vec<_>>()
+        .par_iter()
+        .map(|window| {
+            let mut variants: HashMap<Vec<u8>, usize> = HashMap::new();
+            for case in cases[window[0]..window[1]].chunk_by(|a, b| a.0 == b.0) {
+                let mut variant: Vec<u8> = case.iter().map(|event| event.2).collect();
+                variant.dedup();
+                *variants.entry(variant).or_insert(0) += 1;
+            }
+            variants
+        })
+        .collect();
+
+    let mut final_variants: HashMap<Vec<u8>, usize> = HashMap::new();
+    for partial in partial_variants {
+        for (variant, count) in partial {
+            *final_variants.entry(variant).or_insert(0) += count;
+        }
+    }
+
+    let mut sorted_variants: Vec<_> = final_variants.into_iter().collect();
+    sorted_variants.sort_by_key(|v| std::cmp::Reverse(v.1));
 
     sorted_variants
 }
+
+// The error of an unknown activity (a String, so it can be sent between the workers)
+fn unknown(activity: &str) -> String {
+    format!("Unknown activity: {}", activity)
+}
+
+fn worker_threads(events: usize) -> usize {
+    // Considers some "sane" value to split the work between workers
+    if events < 256 {
+        return 1;
+    }
+    rayon::current_num_threads().max(1)
+}
+
+// Splits the work per thead:
+// * finds split points where case_id "just alters"
+// * this way we process each subset separately with no overlapping case_id (so merging is trivial, and cheap)
+// * returns strictly increasing points from 0 to cases.len() (no empty chunks, a case spanning
+//   several chunks makes fewer of them)
+pub fn split_points<T>(cases: &[T], num_threads: usize, same_case: impl Fn(&T, &T) -> bool) -> Vec<usize> {
+    let chunk_size = cases.len() / num_threads.max(1);
+    let mut splits = Vec::with_capacity(num_threads + 1);
+    splits.push(0);
+
+    for i in 1..num_threads {
+        let mut split_point = (i * chunk_size).max(splits[splits.len() - 1]);
+        // Moves forward to the first event of the next case
+        while split_point > 0 && split_point < cases.len() && same_case(&cases[split_point], &cases[split_point - 1]) {
+            split_point += 1;
+        }
+        if split_point > splits[splits.len() - 1] && split_point < cases.len() {
+            splits.push(split_point);
+        }
+    }
+    if !cases.is_empty() {
+        splits.push(cases.len());
+    }
+    splits
+}
@@ -0,0 +1,131 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  patterns: Frequent sequential patterns (PrefixSpan) in the activity sequences of the cases
+//          * a pattern is a subsequence of the variant of a case (other activities may be in
+//            between, at most `max_gap` of them between two activities of the pattern)
+//          * support: the number of cases with the pattern (at least `min_support` of all cases)
+//          * mined over the variants weighted by their number of cases (a variant is one
+//            sequence), so the number of distinct variants with a pattern comes for free
+//          * ranked by the number of variants the pattern occurs in, then by support and length:
+//            sub-behaviors shared by many variants come first
+//
+use crate::activities;
+
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+pub struct Miner {
+    // fraction of all cases
+    pub min_support: f64,
+    // activities allowed between two activities of a pattern (None: any number)
+    pub max_gap: Option<usize>,
+    pub min_length: usize,
+    pub max_length: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub activities: Vec<u8>,
+    // number of cases
+    pub support: usize,
+    pub share: f64,
+    // number of distinct variants
+    pub variants: usize,
+}
+
+// Projected database: the variants with the prefix and the positions where (an occurrence of)
+// the prefix ends in them
+type Projection = Vec<(usize, Vec<usize>)>;
+
+impl Miner {
+    pub fn mine(&self, variants: &[(Vec<u8>, usize)]) -> Vec<Pattern> {
+        let cases: usize = variants.iter().map(|(_, count)| count).sum();
+        let min_count = ((self.min_support * cases as f64).ceil() as usize).max(1);
+
+        // Single activities: their positions in each variant
+        let mut initial: BTreeMap<u8, Projection> = BTreeMap::new();
+        for (index, (variant, _)) in variants.iter().enumerate() {
+            for (position, &activity) in variant.iter().enumerate() {
+                let projection = initial.entry(activity).or_default();
+                match projection.last_mut() {
+                    Some((last, positions)) if *last == index => positions.push(position),
+                    _ => projection.push((index, vec![position])),
+                }
+            }
+        }
+
+        let mut patterns = Vec::new();
+        for (activity, projection) in initial {
+            self.grow(variants, min_count, cases, &mut vec![activity], projection, &mut patterns);
+        }
+        patterns.sort_by(|a, b| {
+            b.variants
+                .cmp(&a.variants)
+                .then_with(|| b.support.cmp(&a.support))
+                .then_with(|| b.activities.len().cmp(&a.activities.len()))
+                .then_with(|| a.activities.cmp(&b.activities))
+        });
+        patterns
+    }
+
+    fn grow(&self, variants: &[(Vec<u8>, usize)], min_count: usize, cases: usize, prefix: &mut Vec<u8>, projection: Projection, patterns: &mut Vec<Pattern>) {
+        let support: usize = projection.iter().map(|(index, _)| variants[*index].1).sum();
+        if support < min_count {
+            return;
+        }
+        if prefix.len() >= self.min_length {
+            patterns.push(Pattern { activities: prefix.clone(), support, share: support as f64 / cases as f64, variants: projection.len() });
+        }
+        if prefix.len() >= self.max_length {
+            return;
+        }
+
+        // Extensions by one activity: where they end after any end of the prefix (within the gap)
+        let mut extensions: BTreeMap<u8, Projection> = BTreeMap::new();
+        for (index, ends) in &projection {
+            let variant = &variants[*index].0;
+            // Without a gap limit, the earliest end of the prefix allows every extension
+            let ends = if self.max_gap.is_none() { &ends[..1] } else { &ends[..] };
+            for &end in ends {
+                let last = self.max_gap.map_or(variant.len(), |gap| variant.len().min(end.saturating_add(gap).saturating_add(2)));
+                for (position, &activity) in variant.iter().enumerate().take(last).skip(end + 1) {
+                    let extension = extensions.entry(activity).or_default();
+                    match extension.last_mut() {
+                        Some((last, positions)) if last == index => {
+                            // Overlapping windows of the ends: positions are kept once
+                            if !positions.contains(&position) {
+                                positions.push(position);
+                            }
+                        }
+                        _ => extension.push((*index, vec![position])),
+                    }
+                }
+            }
+        }
+        for (activity, extension) in extensions {
+            // Only the earliest end is used without a gap limit
+            let extension = match self.max_gap {
+                None => extension.into_iter().map(|(index, positions)| (index, vec![positions.into_iter().min().unwrap_or_default()])).collect(),
+                Some(_) => extension,
+            };
+            prefix.push(activity);
+            self.grow(variants, min_count, cases, prefix, extension, patterns);
+            prefix.pop();
+        }
+    }
+}
+
+pub fn to_json(patterns: &[Pattern], top: usize, with_names: bool) -> Value {
+    json!(patterns
+        .iter()
+        .take(top)
+        .map(|pattern| {
+            let activities = match with_names {
+                true => json!(pattern.activities.iter().map(|&num| activities::num_to_str(num)).collect::<Vec<_>>()),
+                false => json!(pattern.activities),
+            };
+            json!({"pattern": activities, "support": pattern.support, "share": pattern.share, "variants": pattern.variants})
+        })
+        .collect::<Vec<_>>())
+}
@@ -0,0 +1,171 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  prediction: Predictive monitoring of running (open) cases
+//          * trains on completed cases (all cases of the training log)
+//          * transition system annotated with remaining times and next activities
+//            (state = prefix of the trace, falls back to the last activity for unseen prefixes,
+//            see transition_system.rs)
+//          * k-NN over prefixes (edit distance on activities, then elapsed time)
+//          * predicts remaining time and next activity of each open case
+//
+use crate::activities;
+use crate::timezones::{self, Zone};
+use crate::traces::Trace;
+use crate::transition_system::{Abstraction, TransitionSystem};
+
+use chrono::{Duration, NaiveDateTime};
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+// One training sample of k-NN: a prefix observed at some point of a completed case
+struct Sample {
+    elapsed: f64,
+    remaining: f64,
+    next: Option<u8>,
+}
+
+// A k-NN candidate, ordered by edit distance, then by the difference of elapsed time
+struct Neighbour<'a> {
+    distance: usize,
+    gap: f64,
+    sample: &'a Sample,
+}
+
+impl Ord for Neighbour<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance.cmp(&other.distance).then_with(|| self.gap.total_cmp(&other.gap))
+    }
+}
+
+impl PartialOrd for Neighbour<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Neighbour<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Neighbour<'_> {}
+
+pub struct Predictor {
+    k: usize,
+    by_prefix: TransitionSystem,
+    by_last_activity: TransitionSystem,
+    // distinct prefixes with all their samples (k-NN searches distinct prefixes only)
+    samples: HashMap<Vec<u8>, Vec<Sample>>,
+}
+
+fn seconds(from: NaiveDateTime, to: NaiveDateTime) -> f64 {
+    (to - from).num_milliseconds() as f64 / 1000.0
+}
+
+impl Predictor {
+    pub fn train(completed: &[Trace], k: usize) -> Predictor {
+        let mut predictor = Predictor {
+            k: k.max(1),
+            by_prefix: TransitionSystem::build(completed, Abstraction::Prefix),
+            by_last_activity: TransitionSystem::build(completed, Abstraction::LastK(1)),
+            samples: HashMap::new(),
+        };
+        for trace in completed {
+            let (start, end) = (trace.timestamps[0], trace.timestamps[trace.timestamps.len() - 1]);
+            for (i, &timestamp) in trace.timestamps.iter().enumerate() {
+                let prefix = &trace.activities[..=i];
+                let remaining = seconds(timestamp, end);
+                let next = trace.activities.get(i + 1).copied();
+
+                predictor.samples
+                    .entry(prefix.to_vec())
+                    .or_default()
+                    .push(Sample { elapsed: seconds(start, timestamp), remaining, next });
+            }
+        }
+        predictor
+    }
+
+    // Transition system prediction: (remaining seconds, next activity, its probability)
+    pub fn predict_transition_system(&self, open: &Trace) -> Option<(f64, Option<u8>, f64)> {
+        if open.activities.is_empty() {
+            return None;
+        }
+        let stats = self.by_prefix.state_of(&open.activities).or_else(|| self.by_last_activity.state_of(&open.activities))?;
+        let (next, probability) = stats.next_activity();
+        Some((stats.mean_remaining(), next, probability))
+    }
+
+    // k-NN prediction: (remaining seconds, next activity, share of neighbours voting for it)
+    pub fn predict_knn(&self, open: &Trace) -> Option<(f64, Option<u8>, f64)> {
+        let elapsed = seconds(open.timestamps[0], open.timestamps[open.timestamps.len() - 1]);
+        // The k nearest so far, the farthest of them on top
+        let mut nearest: BinaryHeap<Neighbour> = BinaryHeap::with_capacity(self.k + 1);
+        for (prefix, samples) in &self.samples {
+            // The length difference is a lower bound of the edit distance
+            let worst = nearest.peek().filter(|_| nearest.len() == self.k).map(|neighbour| neighbour.distance);
+            if worst.is_some_and(|worst| prefix.len().abs_diff(open.activities.len()) > worst) {
+                continue;
+            }
+            let distance = edit_distance(prefix, &open.activities);
+            for sample in samples {
+                nearest.push(Neighbour { distance, gap: (sample.elapsed - elapsed).abs(), sample });
+                if nearest.len() > self.k {
+                    nearest.pop();
+                }
+            }
+        }
+        if nearest.is_empty() {
+            return None;
+        }
+        let k = nearest.len();
+
+        let remaining = nearest.iter().map(|neighbour| neighbour.sample.remaining).sum::<f64>() / k as f64;
+        let mut votes: HashMap<Option<u8>, usize> = HashMap::new();
+        for neighbour in &nearest {
+            *votes.entry(neighbour.sample.next).or_insert(0) += 1;
+        }
+        let (next, count) = votes.into_iter().max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))?;
+        Some((remaining, next, count as f64 / k as f64))
+    }
+
+    // Times in the display zone (with its offset), or UTC without one
+    pub fn predict(&self, open_cases: &[Trace], zone: Option<&Zone>) -> Value {
+        let to_json = |open: &Trace, prediction: Option<(f64, Option<u8>, f64)>| match prediction {
+            Some((remaining, next, probability)) => json!({
+                "remaining_seconds": remaining,
+                "expected_end": timezones::display(open.timestamps[open.timestamps.len() - 1] + Duration::milliseconds((remaining * 1000.0) as i64), zone),
+                "next_activity": next.map(activities::num_to_str),
+                "probability": probability,
+            }),
+            None => Value::Null,
+        };
+        json!(open_cases
+            .iter()
+            .map(|open| json!({
+                "case_id": open.case_id,
+                "last_activity": open.activities.last().map(|&num| activities::num_to_str(num)),
+                "last_timestamp": open.timestamps.last().map(|&timestamp| timezones::display(timestamp, zone)),
+                "transition_system": to_json(open, self.predict_transition_system(open)),
+                "knn": to_json(open, self.predict_knn(open)),
+            }))
+            .collect::<Vec<_>>())
+    }
+}
+
+fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, x) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(x != y);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
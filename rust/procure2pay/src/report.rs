@@ -0,0 +1,252 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  report: Self-contained HTML report (one static file, no external assets or scripts)
+//          * log statistics (events, cases, variants, time range, throughput times)
+//          * variant table with bars and cumulative coverage of the cases
+//          * directly-follows graph as inline SVG (arc diagram: activities ordered by their
+//            mean position in the traces, forward edges on the right, backward on the left)
+//          * throughput-time histograms of all cases and of the top variants
+//          * times in UTC, or in a chosen time zone (with its offset)
+//
+use crate::activities;
+use crate::dfg;
+use crate::timezones::{self, Zone};
+use crate::traces::Trace;
+
+use chrono::NaiveDateTime;
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt::Write;
+
+const TOP_VARIANTS: usize = 25;
+const HISTOGRAM_VARIANTS: usize = 5;
+const HISTOGRAM_BINS: usize = 20;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.6em; } h2 { font-size: 1.2em; margin-top: 2em; }
+table { border-collapse: collapse; }
+td, th { padding: 0.2em 0.6em; text-align: left; vertical-align: top; border-bottom: 1px solid #ddd; }
+td.number { text-align: right; white-space: nowrap; }
+.bar { background: #4a7ebb; height: 0.9em; display: inline-block; }
+.activities { font-size: 0.85em; color: #555; }
+svg text { font-family: sans-serif; font-size: 11px; }
+";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn percent(count: usize, total: usize) -> f64 {
+    if total == 0 { 0.0 } else { 100.0 * count as f64 / total as f64 }
+}
+
+// Statistics of the log (its traces) and its variants
+#[derive(Debug, PartialEq)]
+pub struct Statistics {
+    pub events: usize,
+    pub cases: usize,
+    pub variants: usize,
+    pub activities: usize,
+    pub first_event: Option<NaiveDateTime>,
+    pub last_event: Option<NaiveDateTime>,
+    // throughput times in days
+    pub mean_days: f64,
+    pub median_days: f64,
+    pub max_days: f64,
+}
+
+impl Statistics {
+    pub fn compute(events: usize, traces: &[Trace], variants: &[(Vec<u8>, usize)]) -> Statistics {
+        let mut durations: Vec<f64> = traces.iter().map(Trace::throughput_days).collect();
+        durations.sort_by(f64::total_cmp);
+        let activities: BTreeSet<u8> = traces.iter().flat_map(|trace| trace.activities.iter().copied()).collect();
+        Statistics {
+            events,
+            cases: traces.len(),
+            variants: variants.len(),
+            activities: activities.len(),
+            first_event: traces.iter().filter_map(|trace| trace.timestamps.first()).min().copied(),
+            last_event: traces.iter().filter_map(|trace| trace.timestamps.last()).max().copied(),
+            mean_days: durations.iter().sum::<f64>() / durations.len().max(1) as f64,
+            median_days: durations.get(durations.len() / 2).copied().unwrap_or(0.0),
+            max_days: durations.last().copied().unwrap_or(0.0),
+        }
+    }
+
+    // Times in the zone (with its offset), or UTC without one
+    pub fn to_json(&self, zone: Option<&Zone>) -> Value {
+        json!({
+            "events": self.events,
+            "cases": self.cases,
+            "variants": self.variants,
+            "activities": self.activities,
+            "first_event": self.first_event.map(|timestamp| timezones::display(timestamp, zone)),
+            "last_event": self.last_event.map(|timestamp| timezones::display(timestamp, zone)),
+            "mean_throughput_days": self.mean_days,
+            "median_throughput_days": self.median_days,
+            "max_throughput_days": self.max_days,
+        })
+    }
+}
+
+fn statistics_table(statistics: &Statistics, zone: Option<&Zone>) -> String {
+    let time = |timestamp: Option<NaiveDateTime>| timestamp.map(|timestamp| timezones::display(timestamp, zone)).unwrap_or_default();
+    let rows = [
+        ("Events", statistics.events.to_string()),
+        ("Cases", statistics.cases.to_string()),
+        ("Variants", statistics.variants.to_string()),
+        ("Activities", statistics.activities.to_string()),
+        ("First event", time(statistics.first_event)),
+        ("Last event", time(statistics.last_event)),
+        ("Mean throughput time", format!("{:.1} days", statistics.mean_days)),
+        ("Median throughput time", format!("{:.1} days", statistics.median_days)),
+        ("Max throughput time", format!("{:.1} days", statistics.max_days)),
+    ];
+    let mut html = String::from("<table>\n");
+    for (name, value) in rows {
+        let _ = writeln!(html, "<tr><th>{}</th><td class=\"number\">{}</td></tr>", name, escape(&value));
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+fn variant_table(variants: &[(Vec<u8>, usize)]) -> String {
+    let total: usize = variants.iter().map(|(_, count)| count).sum();
+    let max = variants.first().map(|(_, count)| *count).unwrap_or(0);
+    let mut html = String::from("<table>\n<tr><th>#</th><th>Cases</th><th></th><th>Share</th><th>Coverage</th><th>Activities</th></tr>\n");
+    let mut covered = 0;
+    for (rank, (variant, count)) in variants.iter().take(TOP_VARIANTS).enumerate() {
+        covered += count;
+        let names: Vec<String> = variant.iter().map(|&num| escape(activities::num_to_str(num))).collect();
+        let _ = writeln!(
+            html,
+            "<tr><td class=\"number\">{}</td><td class=\"number\">{}</td><td><span class=\"bar\" style=\"width: {:.0}px\"></span></td>\
+             <td class=\"number\">{:.1} %</td><td class=\"number\">{:.1} %</td><td class=\"activities\">{}</td></tr>",
+            rank + 1, count, 200.0 * *count as f64 / max.max(1) as f64, percent(*count, total), percent(covered, total), names.join(" &rarr; "),
+        );
+    }
+    html.push_str("</table>\n");
+    if variants.len() > TOP_VARIANTS {
+        let _ = writeln!(html, "<p>{} more variants with {} cases are not listed.</p>", variants.len() - TOP_VARIANTS, total - covered);
+    }
+    html
+}
+
+fn dfg_svg(traces: &[Trace]) -> String {
+    let dfg = dfg::discover(traces);
+    let nodes = dfg.ordered_activities();
+    let edges = &dfg.edges;
+    let row: HashMap<u8, usize> = nodes.iter().enumerate().map(|(i, activity)| (*activity, i)).collect();
+
+    const ROW: f64 = 34.0;
+    const NODE_WIDTH: f64 = 280.0;
+    const LEFT: f64 = 260.0;
+    let width = 2.0 * LEFT + NODE_WIDTH;
+    let height = ROW * nodes.len() as f64 + 20.0;
+    let y = |activity: u8| 20.0 + ROW * row[&activity] as f64 + ROW / 2.0 - 7.0;
+    let max = edges.values().copied().max().unwrap_or(1) as f64;
+
+    let mut svg = String::new();
+    let _ = writeln!(svg, "<svg width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">", width, height, width, height);
+    for (&(from, to), &count) in edges {
+        let (y1, y2) = (y(from), y(to));
+        // Forward edges bend to the right, backward ones to the left (further for longer jumps)
+        let bend = 20.0 + (y2 - y1).abs() * 0.35;
+        let (x, control, color) = match y2 > y1 {
+            true => (LEFT + NODE_WIDTH, LEFT + NODE_WIDTH + bend, "#4a7ebb"),
+            false => (LEFT, LEFT - bend, "#c0504d"),
+        };
+        let _ = writeln!(
+            svg,
+            "<path d=\"M {x:.1} {y1:.1} C {control:.1} {y1:.1}, {control:.1} {y2:.1}, {x:.1} {y2:.1}\" fill=\"none\" stroke=\"{color}\" stroke-opacity=\"0.6\" stroke-width=\"{:.1}\"><title>{} &rarr; {}: {}</title></path>",
+            1.0 + 5.0 * count as f64 / max, escape(activities::num_to_str(from)), escape(activities::num_to_str(to)), count,
+        );
+        let _ = writeln!(svg, "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" fill=\"{}\">{}</text>", x + (control - x) * 0.75, (y1 + y2) / 2.0 + 4.0, color, count);
+    }
+    for activity in &nodes {
+        let count = dfg.activities[activity].0;
+        let top = 20.0 + ROW * row[activity] as f64;
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{}\" y=\"{:.1}\" width=\"{}\" height=\"{:.1}\" rx=\"4\" fill=\"#eef3fa\" stroke=\"#4a7ebb\"/><text x=\"{}\" y=\"{:.1}\" text-anchor=\"middle\">{} ({})</text>",
+            LEFT, top, NODE_WIDTH, ROW - 14.0, LEFT + NODE_WIDTH / 2.0, top + (ROW - 14.0) / 2.0 + 4.0, escape(activities::num_to_str(*activity)), count,
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Histogram of throughput times (days) over `HISTOGRAM_BINS` bins from zero to `max_days`
+fn histogram_svg(durations: &[f64], max_days: f64) -> String {
+    let bin_width = if max_days > 0.0 { max_days / HISTOGRAM_BINS as f64 } else { 1.0 };
+    let mut bins = [0usize; HISTOGRAM_BINS];
+    for duration in durations {
+        bins[((duration / bin_width) as usize).min(HISTOGRAM_BINS - 1)] += 1;
+    }
+    let highest = bins.iter().copied().max().unwrap_or(0).max(1) as f64;
+
+    const WIDTH: f64 = 600.0;
+    const HEIGHT: f64 = 120.0;
+    let bar = WIDTH / HISTOGRAM_BINS as f64;
+    let mut svg = String::new();
+    let _ = writeln!(svg, "<svg width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">", WIDTH, HEIGHT + 20.0, WIDTH, HEIGHT + 20.0);
+    for (i, &count) in bins.iter().enumerate() {
+        let height = HEIGHT * count as f64 / highest;
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#4a7ebb\"><title>{:.1}-{:.1} days: {} cases</title></rect>",
+            i as f64 * bar + 1.0, HEIGHT - height, bar - 2.0, height, i as f64 * bin_width, (i + 1) as f64 * bin_width, count,
+        );
+    }
+    let _ = writeln!(svg, "<line x1=\"0\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#888\"/>", HEIGHT, WIDTH, HEIGHT);
+    let _ = writeln!(svg, "<text x=\"0\" y=\"{}\">0 days</text>", HEIGHT + 15.0);
+    let _ = writeln!(svg, "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{:.1} days</text>", WIDTH, HEIGHT + 15.0, bin_width * HISTOGRAM_BINS as f64);
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn histograms(traces: &[Trace], variants: &[(Vec<u8>, usize)]) -> String {
+    let mut by_variant: HashMap<&[u8], Vec<f64>> = HashMap::new();
+    for trace in traces {
+        by_variant.entry(&trace.activities).or_default().push(trace.throughput_days());
+    }
+    let all: Vec<f64> = traces.iter().map(Trace::throughput_days).collect();
+    // The same scale for all histograms, so they can be compared
+    let max_days = all.iter().copied().fold(0.0, f64::max);
+
+    let mut html = format!("<h3>All cases</h3>\n{}", histogram_svg(&all, max_days));
+    for (rank, (variant, count)) in variants.iter().take(HISTOGRAM_VARIANTS).enumerate() {
+        let durations = by_variant.get(variant.as_slice()).map(Vec::as_slice).unwrap_or_default();
+        let _ = write!(html, "<h3>Variant {} ({} cases)</h3>\n{}", rank + 1, count, histogram_svg(durations, max_days));
+    }
+    html
+}
+
+// Renders the report of the log (its traces) and the variants (as printed by the engines)
+pub fn render(title: &str, events: usize, traces: &[Trace], variants: &[(Vec<u8>, usize)], zone: Option<&Zone>) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+         <h1>{title}</h1>\n<h2>Log statistics</h2>\n{}<h2>Variants</h2>\n{}<h2>Directly-follows graph</h2>\n{}\
+         <h2>Throughput times</h2>\n{}</body>\n</html>\n",
+        statistics_table(&Statistics::compute(events, traces, variants), zone),
+        variant_table(variants),
+        dfg_svg(traces),
+        histograms(traces, variants),
+        title = escape(title),
+    )
+}
+
+pub fn write(
+    file_path: &str,
+    title: &str,
+    events: usize,
+    traces: &[Trace],
+    variants: &[(Vec<u8>, usize)],
+    zone: Option<&Zone>,
+) -> Result<(), Box<dyn Error>> {
+    std::fs::write(file_path, render(title, events, traces, variants, zone))
+        .map_err(|err| format!("Failed to write report {}: {}", file_path, err).into())
+}
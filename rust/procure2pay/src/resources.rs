@@ -0,0 +1,128 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  resources: Organizational mining from resource attributes of events (user, department, ...)
+//          * handover-of-work social network (who passes the case to whom)
+//          * activity-resource matrix and workload over time (per month of the display zone)
+//          * segregation-of-duties checks (same resource performing both activities of a case)
+//
+use crate::csv_parser::{Attributes, Event};
+use crate::timezones::Zone;
+
+use chrono::NaiveDateTime;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+
+// An event reduced to what we need here: (case_id, timestamp, activity_name, resource)
+type ResourceEvent = (String, NaiveDateTime, String, String);
+
+// Segregation-of-duties rule: (activity A, activity B)
+pub type SodRule = (String, String);
+
+pub struct ResourceAnalysis {
+    // (from resource, to resource) -> number of handovers
+    pub handovers: BTreeMap<(String, String), usize>,
+    // activity -> resource -> number of events
+    pub activity_resources: BTreeMap<String, BTreeMap<String, usize>>,
+    // resource -> month (YYYY-MM) -> number of events
+    pub workload: BTreeMap<String, BTreeMap<String, usize>>,
+    // (activity A, activity B) -> violating (case_id, resource)
+    pub sod_violations: Vec<(SodRule, Vec<(String, String)>)>,
+}
+
+// Parses a segregation-of-duties rule: two activities separated by '|'
+pub fn parse_sod_rule(rule: &str) -> Result<SodRule, Box<dyn Error>> {
+    match rule.split_once('|') {
+        Some((a, b)) if !a.trim().is_empty() && !b.trim().is_empty() => Ok((a.trim().to_string(), b.trim().to_string())),
+        _ => Err(format!("Invalid segregation-of-duties rule (expected \"A|B\"): {}", rule).into()),
+    }
+}
+
+// The workload is counted per month of the display zone (of UTC without one)
+pub fn analyze(events: Vec<(Event, Attributes)>, resource_column: &str, sod_rules: &[SodRule], zone: Option<&Zone>) -> Result<ResourceAnalysis, Box<dyn Error>> {
+    let mut cases: Vec<ResourceEvent> = Vec::with_capacity(events.len());
+    for ((case_id, timestamp, activity_name), mut attributes) in events {
+        let resource = attributes
+            .remove(resource_column)
+            .ok_or_else(|| format!("Missing resource column: {}", resource_column))?;
+        cases.push((case_id, timestamp, activity_name, resource));
+    }
+    // Same ordering as the engines (case_id, timestamp, activity name)
+    cases.sort_unstable_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)).then_with(|| a.2.cmp(&b.2)));
+
+    let mut analysis = ResourceAnalysis {
+        handovers: BTreeMap::new(),
+        activity_resources: BTreeMap::new(),
+        workload: BTreeMap::new(),
+        sod_violations: sod_rules.iter().map(|rule| (rule.clone(), Vec::new())).collect(),
+    };
+
+    for (i, (case_id, timestamp, activity_name, resource)) in cases.iter().enumerate() {
+        *analysis.activity_resources
+            .entry(activity_name.clone()).or_default()
+            .entry(resource.clone()).or_insert(0) += 1;
+        *analysis.workload
+            .entry(resource.clone()).or_default()
+            .entry(zone.map_or(*timestamp, |zone| zone.to_local(*timestamp)).format("%Y-%m").to_string()).or_insert(0) += 1;
+
+        if i > 0 && &cases[i - 1].0 == case_id && &cases[i - 1].3 != resource {
+            *analysis.handovers.entry((cases[i - 1].3.clone(), resource.clone())).or_insert(0) += 1;
+        }
+    }
+
+    // Segregation of duties: resources performing both activities within one case
+    for case in cases.chunk_by(|a, b| a.0 == b.0) {
+        for ((a, b), violations) in analysis.sod_violations.iter_mut() {
+            let performed = |activity: &String| -> BTreeSet<&String> {
+                case.iter().filter(|event| &event.2 == activity).map(|event| &event.3).collect()
+            };
+            for resource in performed(a).intersection(&performed(b)) {
+                violations.push((case[0].0.clone(), resource.to_string()));
+            }
+        }
+    }
+
+    Ok(analysis)
+}
+
+impl ResourceAnalysis {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "handovers": self.handovers
+                .iter()
+                .map(|((from, to), count)| json!({"from": from, "to": to, "count": count}))
+                .collect::<Vec<_>>(),
+            "activity_resources": self.activity_resources,
+            "workload": self.workload,
+            "segregation_of_duties": self.sod_violations
+                .iter()
+                .map(|((a, b), violations)| json!({
+                    "activities": [a, b],
+                    "violations": violations
+                        .iter()
+                        .map(|(case_id, resource)| json!({"case_id": case_id, "resource": resource}))
+                        .collect::<Vec<_>>(),
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    // Handover-of-work network in Graphviz DOT format
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph handover {\n");
+        let resources: BTreeSet<&String> = self.handovers.keys().flat_map(|(from, to)| [from, to]).collect();
+        for resource in resources {
+            dot.push_str(&format!("    \"{}\";\n", escape(resource)));
+        }
+        for ((from, to), count) in &self.handovers {
+            dot.push_str(&format!("    \"{}\" -> \"{}\" [label=\"{}\", penwidth={:.1}];\n", escape(from), escape(to), count, 1.0 + (*count as f64).ln()));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
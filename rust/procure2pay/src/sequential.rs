@@ -4,24 +4,63 @@
 //  sequential: The naive solution (sequential, single-threaded solution).
 //
 use crate::activities;
+use crate::assignments::VariantCases;
 
 use chrono::NaiveDateTime;
 use std::collections::{HashMap};
+use std::error::Error;
 
-pub fn process_cases(cases: Vec<(String, NaiveDateTime, String)>) -> Vec<(Vec<u8>, usize)> {
+// Variants with their number of cases, the most frequent first
+type Variants = Vec<(Vec<u8>, usize)>;
+
+pub fn process_cases(cases: Vec<(String, NaiveDateTime, String)>) -> Result<Variants, Box<dyn Error>> {
+
+    // Counts the unique activity sequences (variants)
+    let mut variant_count: HashMap<Vec<u8>, usize> = HashMap::new();
+    for (_, variant) in case_variants(cases)? {
+        *variant_count.entry(variant).or_insert(0) += 1;
+    }
+
+    // Sorts the variants by their count to get the "top" variants (note: don't need to sort the
+    // entire collection if we're interested in top 10 variants only)
+    let mut sorted_variants: Vec<_> = variant_count.into_iter().collect();
+    sorted_variants.sort_by_key(|v| std::cmp::Reverse(v.1));
+
+    Ok(sorted_variants)
+}
+
+// Same as process_cases(), but keeps the case ids of each variant (instead of their count)
+pub fn process_cases_with_case_ids(cases: Vec<(String, NaiveDateTime, String)>) -> Result<VariantCases, Box<dyn Error>> {
+    let mut variant_cases: HashMap<Vec<u8>, Vec<String>> = HashMap::new();
+    for (case_id, variant) in case_variants(cases)? {
+        variant_cases.entry(variant).or_default().push(case_id);
+    }
+
+    // Case ids in order (and the variants with the same count too), so the output is stable
+    let mut sorted_variants: Vec<_> = variant_cases.into_iter().collect();
+    for (_, case_ids) in sorted_variants.iter_mut() {
+        case_ids.sort();
+    }
+    sorted_variants.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.1.cmp(&b.1)));
+
+    Ok(sorted_variants)
+}
+
+// Creates the variant of each case (an error for an unknown activity)
+fn case_variants(cases: Vec<(String, NaiveDateTime, String)>) -> Result<HashMap<String, Vec<u8>>, Box<dyn Error>> {
 
     // Creates a map on case_id, with list of all activities (with timestamps)
     let mut case_activities: HashMap<String, Vec<(NaiveDateTime, String)>> = HashMap::new();
     for (case_id, timestamp, activity_name) in cases {
         case_activities.entry(case_id)
-            .or_insert_with(Vec::new)
+            .or_default()
             .push((timestamp, activity_name));
     }
 
-    // Now we create variants for each case and count them
-    let mut variant_count: HashMap<Vec<u8>, usize> = HashMap::new();
+    // Now we create variants for each case
+    let mut variants: HashMap<String, Vec<u8>> = HashMap::new();
 
-    for activities in case_activities.values_mut() {
+    for (case_id, mut activities) in case_activities {
         // Need to sort the activities chronologically
         // ...and then by activity name (note: this is a "naive" approach, and if we see various
         // activities with the same timestamp, putting them in alphabetic order solve potential
@@ -32,22 +71,16 @@ pub fn process_cases(cases: Vec<(String, NaiveDateTime, String)>) -> Vec<(Vec<u8
         // De-duplicate activities (again probably due to auto-transitions), so we can
         // simply count the unique variants
         let mut deduped_activities: Vec<u8> = Vec::new();
-        for (_, activity_name) in activities {
-            let activity_number = activities::str_to_num(&activity_name);
+        for (_, activity_name) in &activities {
+            let activity_number = activities::check(activity_name)?;
             // Deduplicate the activity sequence
             if deduped_activities.last().map(|&last| last != activity_number).unwrap_or(true) {
                 deduped_activities.push(activity_number);
             }
         }
 
-        // Counts the unique activity sequence (variant)
-        *variant_count.entry(deduped_activities).or_insert(0) += 1;
+        variants.insert(case_id, deduped_activities);
     }
 
-    // Sorts the variants by their count to get the "top" variants (note: don't need to sort the
-    // entire collection if we're interested in top 10 variants only)
-    let mut sorted_variants: Vec<_> = variant_count.into_iter().collect();
-    sorted_variants.sort_by(|a, b| b.1.cmp(&a.1));
-
-    sorted_variants
+    Ok(variants)
 }
@@ -0,0 +1,216 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  server: Local HTTP API for variant analysis (`procure2pay serve`)
+//          * logs are uploaded (CSV body) or read from a file under the data directory,
+//            and cached in memory by name between queries
+//          * variants (engine, top-N, names), directly-follows graph and statistics of a log,
+//            all with case filters from the query parameters (see filters.rs)
+//          * requests are handled one at a time (the engines use all cores anyway)
+//
+//  GET    /logs                      cached logs (name, events)
+//  POST   /logs?name=N[&path=FILE]   caches the CSV in the body (or the file) as log N
+//                 [&tz=ZONE]         with the timestamps without an offset in this time zone (also the
+//                                    zone of the times of the filters and of /stats, otherwise UTC)
+//  DELETE /logs/N                    drops log N from the cache
+//  GET    /logs/N/variants           ?engine=parallel|gold|interned&top=10&with_names=true
+//  GET    /logs/N/dfg
+//  GET    /logs/N/stats
+//
+use crate::activities;
+use crate::csv_parser::{self, Event};
+use crate::dfg;
+use crate::filters::Filters;
+use crate::report::Statistics;
+use crate::timezones::Zone;
+use crate::traces;
+use crate::{interned, parallel, sequential};
+
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_TOP: usize = 10;
+
+pub struct Api {
+    // `path=` of uploads is resolved within this directory only
+    data_dir: PathBuf,
+    logs: BTreeMap<String, Log>,
+}
+
+struct Log {
+    events: Vec<Event>,
+    // zone of the source (None: UTC, times shown without an offset)
+    zone: Option<Zone>,
+}
+
+// Decodes a percent-encoded URL component ('+' is a space in query strings)
+fn decode(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = component.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 2;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(name), decode(value))
+        })
+        .collect()
+}
+
+fn parameter<'a>(parameters: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    parameters.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+}
+
+fn error(status: u16, message: impl std::fmt::Display) -> (u16, Value) {
+    (status, json!({"error": message.to_string()}))
+}
+
+impl Api {
+    pub fn new(data_dir: &Path) -> Api {
+        Api { data_dir: data_dir.to_path_buf(), logs: BTreeMap::new() }
+    }
+
+    // Caches the events as log `name` (replacing a log of the same name); all activities must be known
+    pub fn add_log(&mut self, name: &str, events: Vec<Event>, zone: Option<Zone>) -> Result<Value, Box<dyn Error>> {
+        if let Some((_, _, activity)) = events.iter().find(|(_, _, activity)| activities::try_str_to_num(activity).is_none()) {
+            return Err(format!("Unknown activity: {}", activity).into());
+        }
+        let summary = json!({"name": name, "events": events.len()});
+        self.logs.insert(name.to_string(), Log { events, zone });
+        Ok(summary)
+    }
+
+    // Reads a CSV file within the data directory (no escaping it with `..` or symlinks)
+    pub fn load_file(&mut self, name: &str, file_path: &str, zone: Option<Zone>) -> Result<Value, Box<dyn Error>> {
+        let data_dir = self.data_dir.canonicalize()?;
+        let path = data_dir.join(file_path).canonicalize().map_err(|err| format!("{}: {}", file_path, err))?;
+        if !path.starts_with(&data_dir) {
+            return Err(format!("Not within the data directory: {}", file_path).into());
+        }
+        let events = csv_parser::parse_csv_in(path.to_str().ok_or("Invalid path")?, &zone.unwrap_or_default())?;
+        self.add_log(name, events, zone)
+    }
+
+    fn variants(events: Vec<Event>, parameters: &[(String, String)]) -> Result<Value, Box<dyn Error>> {
+        let top = match parameter(parameters, "top") {
+            Some(top) => top.parse::<usize>().map_err(|_| format!("Invalid top: {}", top))?,
+            None => DEFAULT_TOP,
+        };
+        let variants = match parameter(parameters, "engine").unwrap_or("parallel") {
+            "parallel" => parallel::process_cases(events)?,
+            "gold" => sequential::process_cases(events)?,
+            "interned" => interned::process_cases(events)?,
+            engine => return Err(format!("Unknown engine (parallel, gold or interned): {}", engine).into()),
+        };
+        let top_variants = variants.into_iter().take(top);
+        Ok(match parameter(parameters, "with_names") == Some("true") {
+            true => json!(top_variants
+                .map(|(variant, count)| (variant.iter().map(|&num| activities::num_to_str(num)).collect::<Vec<_>>(), count))
+                .collect::<Vec<_>>()),
+            false => json!(top_variants.collect::<Vec<_>>()),
+        })
+    }
+
+    fn statistics(events: Vec<Event>, zone: Option<&Zone>) -> Result<Value, Box<dyn Error>> {
+        let count = events.len();
+        let variants = parallel::process_cases(events.clone())?;
+        Ok(Statistics::compute(count, &traces::build_traces(events)?, &variants).to_json(zone))
+    }
+
+    // Handles one request, returns the status code and the JSON body
+    pub fn handle(&mut self, method: &str, url: &str, body: &[u8]) -> (u16, Value) {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let parameters = parse_query(query);
+        let segments: Vec<String> = path.split('/').filter(|segment| !segment.is_empty()).map(decode).collect();
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+        match (method, segments.as_slice()) {
+            ("GET", ["logs"]) => {
+                (200, json!(self.logs.iter().map(|(name, log)| json!({"name": name, "events": log.events.len()})).collect::<Vec<_>>()))
+            }
+            ("POST", ["logs"]) => {
+                let name = match parameter(&parameters, "name") {
+                    Some(name) if !name.is_empty() => name.to_string(),
+                    _ => return error(400, "Missing log name (?name=...)"),
+                };
+                let zone = match parameter(&parameters, "tz").map(Zone::parse).transpose() {
+                    Ok(zone) => zone,
+                    Err(err) => return error(400, err),
+                };
+                let result = match parameter(&parameters, "path") {
+                    Some(file_path) => self.load_file(&name, file_path, zone),
+                    None => csv_parser::parse_csv_from_in(body, &zone.unwrap_or_default()).and_then(|events| self.add_log(&name, events, zone)),
+                };
+                match result {
+                    Ok(summary) => (201, summary),
+                    Err(err) => error(400, err),
+                }
+            }
+            ("DELETE", ["logs", name]) => match self.logs.remove(*name) {
+                Some(_) => (200, json!({"name": name})),
+                None => error(404, format!("Unknown log: {}", name)),
+            },
+            ("GET", ["logs", name, resource @ ("variants" | "dfg" | "stats")]) => {
+                let log = match self.logs.get(*name) {
+                    Some(log) => log,
+                    None => return error(404, format!("Unknown log: {}", name)),
+                };
+                let events = match Filters::parse(&parameters, &log.zone.unwrap_or_default()) {
+                    Ok(filters) => filters.apply(&log.events),
+                    Err(err) => return error(400, err),
+                };
+                let result = match *resource {
+                    "variants" => Api::variants(events, &parameters),
+                    "dfg" => traces::build_traces(events).map(|traces| dfg::discover(&traces).to_json()),
+                    _ => Api::statistics(events, log.zone.as_ref()),
+                };
+                match result {
+                    Ok(json) => (200, json),
+                    Err(err) => error(400, err),
+                }
+            }
+            _ => error(404, format!("Unknown request: {} {}", method, path)),
+        }
+    }
+}
+
+pub fn bind(address: &str) -> Result<tiny_http::Server, Box<dyn Error>> {
+    tiny_http::Server::http(address).map_err(|err| format!("Failed to listen on {}: {}", address, err).into())
+}
+
+// Serves the requests until the process is stopped
+pub fn run(server: &tiny_http::Server, api: &mut Api) -> Result<(), Box<dyn Error>> {
+    let content_type = tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).map_err(|_| "Invalid header")?;
+    for mut request in server.incoming_requests() {
+        let mut body = Vec::new();
+        let (status, json) = match request.as_reader().read_to_end(&mut body) {
+            Ok(_) => api.handle(request.method().as_str(), request.url(), &body),
+            Err(err) => error(400, err),
+        };
+        let response = tiny_http::Response::from_string(json.to_string())
+            .with_status_code(status)
+            .with_header(content_type.clone());
+        // A client gone away is not an error of the server
+        let _ = request.respond(response);
+    }
+    Ok(())
+}
@@ -0,0 +1,196 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  simulation: Discrete-event simulation of the discovered process (what-if analysis)
+//          * discovers routing probabilities (last `history` activities -> next activity) and
+//            the durations before each activity from the log
+//          * applies user overrides (scaled durations, scaled routing to an activity)
+//          * simulates N cases and compares throughput times and variant shares with the as-is log
+//
+use crate::activities;
+use crate::generator::Rng;
+use crate::traces::Trace;
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::error::Error;
+
+// Activity of a simulated step (None = end of a case)
+type Step = Option<u8>;
+
+// Upper bound of activities in one simulated case (the routing may contain loops)
+const MAX_CASE_LENGTH: usize = 256;
+const HISTOGRAM_BINS: usize = 20;
+
+#[derive(Clone)]
+pub struct Model {
+    // number of previous activities the routing depends on
+    history: usize,
+    // previous activities (empty = start) -> [(next activity (None = end), weight)]
+    routing: HashMap<Vec<u8>, Vec<(Step, f64)>>,
+    // activity -> observed durations (seconds) from the previous activity
+    durations: HashMap<u8, Vec<f64>>,
+}
+
+// What-if overrides, per activity: multiplies durations before it, or probability of routing to it
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    pub duration_factors: HashMap<u8, f64>,
+    pub routing_factors: HashMap<u8, f64>,
+}
+
+// Parses an override, e.g. `Post invoice in MM=0.5`
+pub fn parse_override(spec: &str) -> Result<(u8, f64), Box<dyn Error>> {
+    let (activity, factor) = spec.rsplit_once('=').ok_or_else(|| format!("Override must be ACTIVITY=FACTOR: {}", spec))?;
+    let activity = activities::try_str_to_num(activity.trim()).ok_or_else(|| format!("Unknown activity: {}", activity))?;
+    let factor = factor.trim().parse::<f64>().map_err(|_| format!("Invalid factor: {}", factor))?;
+    if factor < 0.0 {
+        return Err(format!("Factor must not be negative: {}", spec).into());
+    }
+    Ok((activity, factor))
+}
+
+impl Model {
+    pub fn discover(traces: &[Trace], history: usize) -> Model {
+        let state = |prefix: &[u8]| prefix[prefix.len().saturating_sub(history)..].to_vec();
+        let mut transitions: HashMap<(Vec<u8>, Step), usize> = HashMap::new();
+        let mut durations: HashMap<u8, Vec<f64>> = HashMap::new();
+        for trace in traces {
+            for (i, &activity) in trace.activities.iter().enumerate() {
+                *transitions.entry((state(&trace.activities[..i]), Some(activity))).or_insert(0) += 1;
+                // (the first activity of a case has no duration, it isn't sampled for the first one either)
+                if i > 0 {
+                    let duration = (trace.timestamps[i] - trace.timestamps[i - 1]).num_milliseconds() as f64 / 1000.0;
+                    durations.entry(activity).or_default().push(duration);
+                }
+            }
+            *transitions.entry((state(&trace.activities), None)).or_insert(0) += 1;
+        }
+
+        let mut routing: HashMap<Vec<u8>, Vec<(Step, f64)>> = HashMap::new();
+        for ((from, to), count) in transitions {
+            routing.entry(from).or_default().push((to, count as f64));
+        }
+        // Deterministic order of choices, so the same seed yields the same simulation
+        for choices in routing.values_mut() {
+            choices.sort_by_key(|(to, _)| *to);
+        }
+        Model { history, routing, durations }
+    }
+
+    pub fn with_overrides(&self, overrides: &Overrides) -> Model {
+        let mut model = self.clone();
+        for (activity, samples) in model.durations.iter_mut() {
+            if let Some(factor) = overrides.duration_factors.get(activity) {
+                samples.iter_mut().for_each(|duration| *duration *= factor);
+            }
+        }
+        for choices in model.routing.values_mut() {
+            for (to, weight) in choices.iter_mut() {
+                if let Some(factor) = to.and_then(|activity| overrides.routing_factors.get(&activity)) {
+                    *weight *= factor;
+                }
+            }
+        }
+        model
+    }
+
+    // Simulates one case: (variant, throughput time in seconds)
+    fn simulate_case(&self, rng: &mut Rng) -> (Vec<u8>, f64) {
+        let mut variant = Vec::new();
+        let mut throughput = 0.0;
+        while variant.len() < MAX_CASE_LENGTH {
+            let state = &variant[variant.len().saturating_sub(self.history)..];
+            let choices = match self.routing.get(state) {
+                Some(choices) if choices.iter().any(|(_, weight)| *weight > 0.0) => choices,
+                _ => break,
+            };
+            let weights: Vec<f64> = choices.iter().map(|(_, weight)| *weight).collect();
+            let activity = match choices[rng.weighted(&weights)].0 {
+                Some(activity) => activity,
+                None => break,
+            };
+            // An activity only seen at the start of cases has no durations (the routing may still lead to it)
+            match self.durations.get(&activity) {
+                Some(samples) if !variant.is_empty() && !samples.is_empty() => throughput += samples[rng.below(samples.len())],
+                _ => {}
+            }
+            variant.push(activity);
+        }
+        (variant, throughput)
+    }
+
+    pub fn simulate(&self, cases: usize, seed: u64) -> Vec<(Vec<u8>, f64)> {
+        let mut rng = Rng::new(seed);
+        (0..cases).map(|_| self.simulate_case(&mut rng)).collect()
+    }
+}
+
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    sorted[((sorted.len() - 1) as f64 * p).round() as usize]
+}
+
+// Throughput-time statistics, histogram and top variant shares of one scenario
+fn summarize(cases: &[(Vec<u8>, f64)], histogram_max: f64, top: usize) -> Value {
+    let mut throughput: Vec<f64> = cases.iter().map(|(_, seconds)| *seconds).collect();
+    throughput.sort_by(|a, b| a.total_cmp(b));
+    let mean = throughput.iter().sum::<f64>() / throughput.len().max(1) as f64;
+
+    let mut histogram = vec![0usize; HISTOGRAM_BINS];
+    for seconds in &throughput {
+        let bin = ((seconds / histogram_max) * HISTOGRAM_BINS as f64) as usize;
+        histogram[bin.min(HISTOGRAM_BINS - 1)] += 1;
+    }
+
+    let mut variants: HashMap<&[u8], usize> = HashMap::new();
+    for (variant, _) in cases {
+        *variants.entry(variant.as_slice()).or_insert(0) += 1;
+    }
+    let mut variants: Vec<_> = variants.into_iter().collect();
+    variants.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    let variants: Vec<_> = variants
+        .into_iter()
+        .take(top)
+        .map(|(variant, count)| json!({
+            "variant": variant.iter().map(|&num| activities::num_to_str(num)).collect::<Vec<_>>(),
+            "share": count as f64 / cases.len() as f64,
+        }))
+        .collect();
+
+    json!({
+        "cases": cases.len(),
+        "throughput_seconds": {
+            "mean": mean,
+            "median": percentile(&throughput, 0.5),
+            "p90": percentile(&throughput, 0.9),
+            "p95": percentile(&throughput, 0.95),
+            "max": throughput.last().copied().unwrap_or(0.0),
+        },
+        "histogram": histogram,
+        "variants": variants,
+    })
+}
+
+// Compares the as-is log with the simulation of the discovered model (baseline, to see the error
+// of the model itself) and with the simulation after applying the overrides (what-if)
+pub fn what_if(traces: &[Trace], history: usize, overrides: &Overrides, cases: usize, seed: u64, top: usize) -> Value {
+    let as_is: Vec<(Vec<u8>, f64)> = traces
+        .iter()
+        .map(|trace| (trace.activities.clone(), (trace.timestamps[trace.timestamps.len() - 1] - trace.timestamps[0]).num_milliseconds() as f64 / 1000.0))
+        .collect();
+    let model = Model::discover(traces, history);
+    let baseline = model.simulate(cases, seed);
+    let what_if = model.with_overrides(overrides).simulate(cases, seed);
+
+    // All histograms share the same bins, so they are comparable
+    let histogram_max = as_is.iter().chain(&baseline).chain(&what_if).map(|(_, seconds)| *seconds).fold(1.0, f64::max);
+    json!({
+        "histogram_bin_seconds": histogram_max / HISTOGRAM_BINS as f64,
+        "as_is": summarize(&as_is, histogram_max, top),
+        "simulated_baseline": summarize(&baseline, histogram_max, top),
+        "what_if": summarize(&what_if, histogram_max, top),
+    })
+}
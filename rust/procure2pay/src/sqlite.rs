@@ -0,0 +1,114 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  sqlite: SQLite ingestion and result persistence
+//          * reads events via a configurable SQL query (columns: case_id, activity, timestamp)
+//          * writes run metadata, variants and case-to-variant assignments into tables
+//
+use crate::activities;
+use crate::csv_parser::Event;
+use crate::timezones::{self, Zone};
+
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection};
+use std::error::Error;
+
+pub const DEFAULT_QUERY: &str = "SELECT case_id, activity, timestamp FROM events";
+
+// Metadata of one run of the analysis
+pub struct Run<'a> {
+    pub source: &'a str,
+    pub engine: &'a str,
+    pub events: usize,
+    pub duration_ms: u128,
+}
+
+fn to_text(value: ValueRef) -> Result<String, Box<dyn Error>> {
+    match value {
+        ValueRef::Text(text) => Ok(String::from_utf8(text.to_vec())?),
+        ValueRef::Integer(number) => Ok(number.to_string()),
+        ValueRef::Real(number) => Ok(number.to_string()),
+        _ => Err("Expected a text or number column".into()),
+    }
+}
+
+// Reads events; the query must return case_id, activity and timestamp (in this order)
+pub fn read_events(db_path: &str, query: &str, zone: &Zone) -> Result<Vec<Event>, Box<dyn Error>> {
+    let connection = Connection::open(db_path)?;
+    let mut statement = connection.prepare(query)?;
+    if statement.column_count() != 3 {
+        return Err("The query must return 3 columns: case_id, activity, timestamp".into());
+    }
+
+    let mut cases = Vec::new();
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let case_id = to_text(row.get_ref(0)?)?;
+        let activity_name = to_text(row.get_ref(1)?)?;
+        let timestamp = timezones::parse_timestamp(&to_text(row.get_ref(2)?)?, zone)?;
+        cases.push((case_id, timestamp, activity_name));
+    }
+    Ok(cases)
+}
+
+// Persists the results of one run (tables are created if needed), returns the run_id
+// * variants: sorted variants as returned by the engines (variant_id is the 1-based rank)
+// * assignments: (case_id, variant_id)
+pub fn persist(db_path: &str, run: &Run, variants: &[(Vec<u8>, usize)], assignments: &[(String, usize)]) -> Result<i64, Box<dyn Error>> {
+    let mut connection = Connection::open(db_path)?;
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS runs (
+             run_id INTEGER PRIMARY KEY AUTOINCREMENT,
+             started_at TEXT NOT NULL,
+             source TEXT NOT NULL,
+             engine TEXT NOT NULL,
+             events INTEGER NOT NULL,
+             cases INTEGER NOT NULL,
+             variants INTEGER NOT NULL,
+             duration_ms INTEGER NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS variants (
+             run_id INTEGER NOT NULL REFERENCES runs(run_id),
+             variant_id INTEGER NOT NULL,
+             cases INTEGER NOT NULL,
+             length INTEGER NOT NULL,
+             activities TEXT NOT NULL,
+             PRIMARY KEY (run_id, variant_id)
+         );
+         CREATE TABLE IF NOT EXISTS case_variants (
+             run_id INTEGER NOT NULL REFERENCES runs(run_id),
+             case_id TEXT NOT NULL,
+             variant_id INTEGER NOT NULL,
+             PRIMARY KEY (run_id, case_id)
+         );",
+    )?;
+
+    let transaction = connection.transaction()?;
+    transaction.execute(
+        "INSERT INTO runs (started_at, source, engine, events, cases, variants, duration_ms) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            chrono::Local::now().naive_local().format("%Y-%m-%d %H:%M:%S").to_string(),
+            run.source,
+            run.engine,
+            run.events as i64,
+            assignments.len() as i64,
+            variants.len() as i64,
+            run.duration_ms as i64,
+        ],
+    )?;
+    let run_id = transaction.last_insert_rowid();
+    {
+        let mut insert_variant = transaction.prepare("INSERT INTO variants (run_id, variant_id, cases, length, activities) VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        for (i, (variant, count)) in variants.iter().enumerate() {
+            // Activities as a JSON array of names (the same as `--with-names` prints)
+            let names = serde_json::to_string(&variant.iter().map(|&num| activities::num_to_str(num)).collect::<Vec<_>>())?;
+            insert_variant.execute(params![run_id, i as i64 + 1, *count as i64, variant.len() as i64, names])?;
+        }
+        let mut insert_case = transaction.prepare("INSERT INTO case_variants (run_id, case_id, variant_id) VALUES (?1, ?2, ?3)")?;
+        for (case_id, variant_id) in assignments {
+            insert_case.execute(params![run_id, case_id, *variant_id as i64])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(run_id)
+}
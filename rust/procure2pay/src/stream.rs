@@ -0,0 +1,236 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  stream: Real-time variant monitoring of newline-delimited events
+//          * reads CSV (`case_id;activity;timestamp`) or JSON lines from stdin (or any reader),
+//            a local TCP socket or a Unix socket
+//          * keeps running per-case traces and variant counts (the incremental engine state)
+//          * publishes top-N snapshots periodically (and at the end of the input)
+//          * finalizes cases idle for longer than the timeout (in event time)
+//
+use crate::activities;
+use crate::csv_parser::Event;
+use crate::incremental::State;
+use crate::timezones::{self, Zone};
+
+use chrono::{Duration, NaiveDateTime};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+pub enum Source {
+    Reader(Box<dyn BufRead + Send>),
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(String),
+}
+
+pub struct Config {
+    pub format: Format,
+    pub top: usize,
+    pub with_names: bool,
+    // Idle cases (no event within this time before the newest event) are finalized
+    pub case_timeout: Option<Duration>,
+    // of the timestamps without an offset (all are converted to UTC, see timezones.rs)
+    pub zone: Zone,
+    // of the watermark in the snapshots (UTC without an offset if None)
+    pub display_zone: Option<Zone>,
+}
+
+// Parses one line, None for empty lines and CSV headers
+pub fn parse_line(line: &str, format: Format, zone: &Zone) -> Result<Option<Event>, Box<dyn Error>> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let (case_id, activity, timestamp) = match format {
+        Format::Csv => {
+            let fields: Vec<&str> = line.split(';').collect();
+            if fields.len() < 3 {
+                return Err(format!("Expected case_id;activity;timestamp: {}", line).into());
+            }
+            // The header (as written by `procure2pay generate`)
+            if fields[2].eq_ignore_ascii_case("timestamp") {
+                return Ok(None);
+            }
+            (fields[0].to_string(), fields[1].to_string(), fields[2].to_string())
+        }
+        Format::Json => {
+            let json: Value = serde_json::from_str(line)?;
+            let field = |name: &str| -> Result<String, Box<dyn Error>> {
+                match &json[name] {
+                    Value::String(value) => Ok(value.clone()),
+                    Value::Number(value) => Ok(value.to_string()),
+                    _ => Err(format!("Missing {}: {}", name, line).into()),
+                }
+            };
+            (field("case_id")?, field("activity")?, field("timestamp")?)
+        }
+    };
+    Ok(Some((case_id, timezones::parse_timestamp(&timestamp, zone)?, activity)))
+}
+
+pub struct Monitor {
+    state: State,
+    config: Config,
+    events: usize,
+    finalized: usize,
+    timed_out: usize,
+    late_events: usize,
+    invalid_lines: usize,
+    // the newest event time seen (the clock of the case timeouts)
+    watermark: Option<NaiveDateTime>,
+}
+
+impl Monitor {
+    pub fn new(state: State, config: Config) -> Monitor {
+        Monitor { state, config, events: 0, finalized: 0, timed_out: 0, late_events: 0, invalid_lines: 0, watermark: None }
+    }
+
+    pub fn process_line(&mut self, line: &str) {
+        let (case_id, timestamp, activity_name) = match parse_line(line, self.config.format, &self.config.zone) {
+            Ok(Some(event)) => event,
+            Ok(None) => return,
+            Err(_) => {
+                self.invalid_lines += 1;
+                return;
+            }
+        };
+        let activity = match activities::try_str_to_num(&activity_name) {
+            Some(activity) => activity,
+            None => {
+                self.invalid_lines += 1;
+                return;
+            }
+        };
+        self.events += 1;
+        self.watermark = self.watermark.max(Some(timestamp));
+        if !self.state.add_event(&case_id, timestamp, activity) {
+            self.late_events += 1;
+        } else if self.state.close_if_ended(&case_id) {
+            self.finalized += 1;
+        }
+    }
+
+    // Finalizes the timed out cases
+    pub fn expire(&mut self) {
+        if let (Some(timeout), Some(watermark)) = (self.config.case_timeout, self.watermark) {
+            self.timed_out += self.state.expire_idle(watermark - timeout);
+        }
+    }
+
+    pub fn snapshot(&self) -> Value {
+        let top: Vec<Value> = self.state
+            .top_variants()
+            .into_iter()
+            .take(self.config.top)
+            .map(|(variant, count)| match self.config.with_names {
+                true => json!([variant.iter().map(|&num| activities::num_to_str(num)).collect::<Vec<_>>(), count]),
+                false => json!([variant, count]),
+            })
+            .collect();
+        json!({
+            "watermark": self.watermark.map(|timestamp| timezones::display(timestamp, self.config.display_zone.as_ref())),
+            "events": self.events,
+            "open_cases": self.state.open_cases(),
+            "finalized_cases": self.finalized,
+            "timed_out_cases": self.timed_out,
+            "late_events": self.late_events,
+            "invalid_lines": self.invalid_lines,
+            "top": top,
+        })
+    }
+
+    pub fn save_state(&self, file_path: &str) -> Result<(), Box<dyn Error>> {
+        self.state.save(file_path)
+    }
+}
+
+fn forward_lines<R: BufRead>(reader: R, lines: Sender<String>) {
+    for line in reader.lines().map_while(Result::ok) {
+        if lines.send(line).is_err() {
+            break;
+        }
+    }
+}
+
+// Reads lines in background threads (one per connection), so snapshots are published
+// even when no events arrive
+fn spawn_readers(source: Source, lines: Sender<String>) -> Result<(), Box<dyn Error>> {
+    match source {
+        Source::Reader(reader) => {
+            thread::spawn(move || forward_lines(reader, lines));
+        }
+        Source::Tcp(address) => {
+            let listener = TcpListener::bind(&address)?;
+            thread::spawn(move || {
+                for connection in listener.incoming().map_while(Result::ok) {
+                    let lines = lines.clone();
+                    thread::spawn(move || forward_lines(BufReader::new(connection), lines));
+                }
+            });
+        }
+        #[cfg(unix)]
+        Source::Unix(path) => {
+            use std::os::unix::fs::FileTypeExt;
+            // Removes a stale socket of a previous run (but never a regular file)
+            if std::fs::metadata(&path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+                std::fs::remove_file(&path)?;
+            }
+            let listener = std::os::unix::net::UnixListener::bind(&path)?;
+            thread::spawn(move || {
+                for connection in listener.incoming().map_while(Result::ok) {
+                    let lines = lines.clone();
+                    thread::spawn(move || forward_lines(BufReader::new(connection), lines));
+                }
+            });
+        }
+    }
+    Ok(())
+}
+
+// Processes the events until the input ends (sockets are listened to until the process is
+// stopped), writes a snapshot every `interval` and after the last event; `on_snapshot` is
+// called before each of them (e.g. to save the state)
+pub fn run<W: Write>(
+    source: Source,
+    monitor: &mut Monitor,
+    interval: std::time::Duration,
+    output: &mut W,
+    mut on_snapshot: impl FnMut(&Monitor) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let (sender, receiver) = mpsc::channel();
+    spawn_readers(source, sender)?;
+
+    let mut next_snapshot = Instant::now() + interval;
+    loop {
+        let finished = match receiver.recv_timeout(next_snapshot.saturating_duration_since(Instant::now())) {
+            Ok(line) => {
+                monitor.process_line(&line);
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => true,
+        };
+        if finished || Instant::now() >= next_snapshot {
+            monitor.expire();
+            on_snapshot(monitor)?;
+            writeln!(output, "{}", monitor.snapshot())?;
+            output.flush()?;
+            next_snapshot = Instant::now() + interval;
+        }
+        if finished {
+            return Ok(());
+        }
+    }
+}
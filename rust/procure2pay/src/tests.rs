@@ -1,22 +1,63 @@
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use chrono::NaiveDateTime;
-    use std::panic;
     use crate::activities;
+    use crate::anomalies;
+    use crate::breakdown;
+    use crate::declare;
+    use crate::resources;
+    use crate::prediction;
+    use crate::generator;
+    use crate::simulation;
+    use crate::sqlite;
+    use crate::columnar;
+    use crate::assignments;
+    use crate::incremental;
+    use crate::stream;
+    use crate::out_of_core;
+    use crate::parallel;
+    use crate::patterns;
+    use crate::report;
+    use crate::server;
+    use crate::filters;
+    use crate::explore;
+    use crate::config;
+    use crate::labels;
+    use crate::csv_parser;
+    use crate::timezones::{self, Zone};
+    use crate::traces;
+    use crate::transition_system::{Abstraction, TransitionSystem};
     #[cfg(feature = "sequential")]
     use crate::sequential;
-    #[cfg(feature = "parallel")]
-    use crate::parallel;
+    #[cfg(all(feature = "interned", not(feature = "sequential")))]
+    use crate::interned;
 
     #[cfg(feature = "sequential")]
     fn process_cases(cases: Vec<(String, NaiveDateTime, String)>) -> Vec<(Vec<u8>, usize)>  {
-        sequential::process_cases(cases)
+        sequential::process_cases(cases).unwrap()
     }
 
-    #[cfg(feature = "parallel")]
+    #[cfg(all(feature = "interned", not(feature = "sequential")))]
     fn process_cases(cases: Vec<(String, NaiveDateTime, String)>) -> Vec<(Vec<u8>, usize)>  {
-        parallel::process_cases(cases)
+        interned::process_cases(cases).unwrap()
+    }
+
+    // Runs the optimized engine unless the golden (or interned) one is explicitly selected
+    #[cfg(all(any(feature = "parallel", not(feature = "sequential")), not(feature = "interned")))]
+    fn process_cases(cases: Vec<(String, NaiveDateTime, String)>) -> Vec<(Vec<u8>, usize)>  {
+        parallel::process_cases(cases).unwrap()
+    }
+
+    #[cfg(feature = "sequential")]
+    fn process_cases_with_case_ids(cases: Vec<(String, NaiveDateTime, String)>) -> assignments::VariantCases {
+        sequential::process_cases_with_case_ids(cases).unwrap()
+    }
+
+    #[cfg(any(feature = "parallel", not(feature = "sequential")))]
+    fn process_cases_with_case_ids(cases: Vec<(String, NaiveDateTime, String)>) -> assignments::VariantCases {
+        parallel::process_cases_with_case_ids(cases).unwrap()
     }
 
     fn parse_date(date_str: &str) -> NaiveDateTime {
@@ -35,7 +76,7 @@ mod tests {
                 // Create activities for each case
                 // For each case, generate activities
                 for (i, &activity_num) in sequence.iter().enumerate() {
-                    let activity_name = activities::num_to_str(activity_num as u8);
+                    let activity_name = activities::num_to_str(activity_num);
                     // let timestamp = start_date + Duration::days(i as i64);
                     let timestamp = current_date + chrono::Duration::days(i as i64);
 
@@ -83,13 +124,21 @@ mod tests {
     #[test]
     fn test_invalid_activity() {
         let cases = vec![
-            ("1".to_string(), parse_date("2024-08-18"), "Activity A".to_string()),
+            ("1".to_string(), parse_date("2024-08-18"), "Clear open item".to_string()),
+            ("2".to_string(), parse_date("2024-08-18"), "Activity A".to_string()),
         ];
 
-        let result = panic::catch_unwind(|| {
-            process_cases(cases);
-        });
-        assert!(result.is_err());
+        // An error of every engine (e.g. a typo in the log), not a panic
+        for result in [
+            parallel::process_cases(cases.clone()).map(|_| ()),
+            parallel::process_cases_with_case_ids(cases.clone()).map(|_| ()),
+            crate::interned::process_cases(cases.clone()).map(|_| ()),
+            crate::sequential::process_cases(cases.clone()).map(|_| ()),
+            crate::sequential::process_cases_with_case_ids(cases.clone()).map(|_| ()),
+            traces::build_traces(cases.clone()).map(|_| ()),
+        ] {
+            assert_eq!(result.unwrap_err().to_string(), "Unknown activity: Activity A");
+        }
     }
 
     #[test]
@@ -173,4 +222,977 @@ mod tests {
         let result = process_cases(cases);
         assert_eq!(result, expected_variants);
     }
+
+    #[test]
+    fn test_declare_rule_parsing() {
+        let rule = declare::parse_rule("precedence(Enter goods receipt, Post invoice in MM)").unwrap();
+        assert_eq!(rule.to_string(), "precedence(Enter goods receipt, Post invoice in MM)");
+        let rule = declare::parse_rule("exactly_once(Change purchase order (currency))").unwrap();
+        assert_eq!(rule.a, activities::str_to_num("Change purchase order (currency)"));
+        assert!(declare::parse_rule("response(Clear open item)").is_err());
+        assert!(declare::parse_rule("eventually(Clear open item)").is_err());
+        assert!(declare::parse_rule("init(Activity A)").is_err());
+    }
+
+    #[test]
+    fn test_declare_check_violations() {
+        // 1: goods receipt before posting the invoice, 2: posted without goods receipt
+        let cases = vec![
+            ("1".to_string(), parse_date("2024-08-17"), "Enter goods receipt".to_string()),
+            ("1".to_string(), parse_date("2024-08-18"), "Post invoice in MM".to_string()),
+            ("2".to_string(), parse_date("2024-08-17"), "Post invoice in MM".to_string()),
+            ("2".to_string(), parse_date("2024-08-18"), "Enter goods receipt".to_string()),
+        ];
+        let rule = declare::parse_rule("precedence(Enter goods receipt, Post invoice in MM)").unwrap();
+        let result = declare::check(&traces::build_traces(cases).unwrap(), &[rule]);
+        assert_eq!(result[0]["violations"], 1);
+        assert_eq!(result[0]["cases"], serde_json::json!(["2"]));
+        assert_eq!(result[0]["variants"], serde_json::json!([[["Post invoice in MM", "Enter goods receipt"], 1]]));
+    }
+
+    #[test]
+    fn test_declare_mining() {
+        let cases = generate_test_cases_from_variants(vec![(vec![6, 8, 4], 9), (vec![8, 4], 1)]);
+        let mined = declare::mine(&traces::build_traces(cases).unwrap(), 0.85, 0.85);
+        let mined: Vec<String> = mined.iter().map(|(constraint, _, _)| constraint.to_string()).collect();
+        assert!(mined.contains(&"precedence(Enter goods receipt, Post invoice in MM)".to_string()));
+        assert!(mined.contains(&"end(Clear open item)".to_string()));
+        assert!(!mined.contains(&"init(Post invoice in MM)".to_string()));
+    }
+
+    #[test]
+    fn test_resources_handover_and_segregation_of_duties() {
+        let event = |case_id: &str, date: &str, activity: &str, user: &str| {
+            let attributes = [("user".to_string(), user.to_string())].into_iter().collect();
+            ((case_id.to_string(), parse_date(date), activity.to_string()), attributes)
+        };
+        let events = vec![
+            event("1", "2024-08-17", "Create purchase order item", "alice"),
+            event("1", "2024-08-18", "Enter goods receipt", "bob"),
+            event("1", "2024-08-19", "Clear open item", "alice"),
+            event("2", "2024-08-17", "Create purchase order item", "alice"),
+            event("2", "2024-08-18", "Clear open item", "bob"),
+        ];
+        let sod = resources::parse_sod_rule("Create purchase order item|Clear open item").unwrap();
+        let analysis = resources::analyze(events, "user", &[sod], None).unwrap();
+
+        assert_eq!(analysis.handovers.get(&("alice".to_string(), "bob".to_string())), Some(&2));
+        assert_eq!(analysis.handovers.get(&("bob".to_string(), "alice".to_string())), Some(&1));
+        assert_eq!(analysis.activity_resources["Clear open item"]["bob"], 1);
+        assert_eq!(analysis.workload["alice"]["2024-08"], 3);
+        assert_eq!(analysis.sod_violations[0].1, vec![("1".to_string(), "alice".to_string())]);
+        assert!(analysis.to_dot().contains("\"alice\" -> \"bob\" [label=\"2\""));
+        assert!(resources::parse_sod_rule("Clear open item").is_err());
+        // Months of the display zone (midnight UTC is still August in Brazil)
+        let events = vec![event("3", "2024-09-01", "Clear open item", "carol")];
+        assert!(resources::analyze(events.clone(), "user", &[], None).unwrap().workload["carol"].contains_key("2024-09"));
+        assert!(resources::analyze(events, "user", &[], Zone::parse("America/Sao_Paulo").ok().as_ref()).unwrap().workload["carol"].contains_key("2024-08"));
+    }
+
+    #[test]
+    fn test_prediction_of_open_cases() {
+        let completed = generate_test_cases_from_variants(vec![(vec![1, 2, 3], 3), (vec![1, 4], 1)]);
+        let predictor = prediction::Predictor::train(&traces::build_traces(completed).unwrap(), 3);
+        let open = traces::build_traces(vec![
+            ("open".to_string(), parse_date("2024-09-01"), activities::num_to_str(1).to_string()),
+            ("open".to_string(), parse_date("2024-09-02"), activities::num_to_str(2).to_string()),
+        ]).unwrap();
+
+        // Each step of the generated cases takes a day
+        assert_eq!(predictor.predict_transition_system(&open[0]), Some((86400.0, Some(3), 1.0)));
+        assert_eq!(predictor.predict_knn(&open[0]), Some((86400.0, Some(3), 1.0)));
+
+        let result = predictor.predict(&open, None);
+        assert_eq!(result[0]["transition_system"]["next_activity"], activities::num_to_str(3));
+        assert_eq!(result[0]["transition_system"]["expected_end"], "2024-09-03 00:00:00");
+        let result = predictor.predict(&open, Zone::parse("Europe/Berlin").ok().as_ref());
+        assert_eq!(result[0]["last_timestamp"], "2024-09-02 02:00:00 +02:00");
+        assert_eq!(result[0]["knn"]["expected_end"], "2024-09-03 02:00:00 +02:00");
+    }
+
+    fn generator_config(cases: usize, seed: u64) -> generator::Config {
+        generator::Config {
+            cases,
+            seed,
+            start: parse_date("2024-08-18"),
+            arrival: generator::Distribution::Exponential(3600.0),
+            durations: std::collections::HashMap::new(),
+            default_duration: generator::Distribution::Uniform(60.0, 86400.0),
+            noise: generator::Noise::default(),
+            shuffle: true,
+        }
+    }
+
+    #[test]
+    fn test_generated_log_is_deterministic() {
+        let model = generator::Model::Variants(vec![
+            (vec![activities::num_to_str(0).to_string(), activities::num_to_str(4).to_string()], 3.0),
+            (vec![activities::num_to_str(9).to_string(), activities::num_to_str(7).to_string(), activities::num_to_str(4).to_string()], 1.0),
+        ]);
+        let first = generator::generate(&model, &generator_config(500, 7)).unwrap();
+        assert_eq!(first, generator::generate(&model, &generator_config(500, 7)).unwrap());
+        assert_ne!(first, generator::generate(&model, &generator_config(500, 8)).unwrap());
+
+        // Shuffled events yield the same variants as the ordered ones
+        let mut ordered_config = generator_config(500, 7);
+        ordered_config.shuffle = false;
+        let ordered = generator::generate(&model, &ordered_config).unwrap();
+        let result = process_cases(first);
+        assert_eq!(result, process_cases(ordered));
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].0, vec![0, 4]);
+        assert_eq!(result.iter().map(|v| v.1).sum::<usize>(), 500);
+    }
+
+    #[test]
+    fn test_generated_log_with_noise() {
+        let model = generator::Model::Variants(vec![(vec![activities::num_to_str(0).to_string(), activities::num_to_str(4).to_string()], 1.0)]);
+        let mut config = generator_config(100, 1);
+        config.noise.missing = 1.0;
+        assert!(generator::generate(&model, &config).unwrap().is_empty());
+
+        let mut config = generator_config(100, 1);
+        config.noise.duplicate = 1.0;
+        let cases = generator::generate(&model, &config).unwrap();
+        assert_eq!(cases.len(), 400);
+        assert_eq!(process_cases(cases), vec![(vec![0, 4], 100)]);
+
+        let mut config = generator_config(100, 1);
+        config.noise.swap = 1.0;
+        assert_eq!(process_cases(generator::generate(&model, &config).unwrap()), vec![(vec![4, 0], 100)]);
+    }
+
+    #[test]
+    fn test_petri_net_simulation() {
+        let transition = |label: u8, inputs: &[&str], outputs: &[&str]| generator::Transition {
+            label: Some(activities::num_to_str(label).to_string()),
+            inputs: inputs.iter().map(|place| place.to_string()).collect(),
+            outputs: outputs.iter().map(|place| place.to_string()).collect(),
+            weight: 1.0,
+        };
+        // The second transition synchronizes p and q (a token from each)
+        let net = |q: usize| generator::Model::PetriNet(generator::PetriNet {
+            transitions: vec![transition(0, &["start"], &["p"]), transition(4, &["p", "q"], &["end"])],
+            initial: [("start".to_string(), 1), ("q".to_string(), q)].into_iter().collect(),
+            final_marking: [("end".to_string(), 1)].into_iter().collect(),
+        });
+        assert_eq!(process_cases(generator::generate(&net(1), &generator_config(10, 1)).unwrap()), vec![(vec![0, 4], 10)]);
+        // Without a token in q it deadlocks before the final marking, an error (not a cut-off trace)
+        assert_eq!(
+            generator::generate(&net(0), &generator_config(10, 1)).err().unwrap().to_string(),
+            "The Petri net deadlocked before reaching its final marking (after 1 transitions)"
+        );
+
+        let net_path = std::env::temp_dir().join(format!("procure2pay_test_{}_net.json", std::process::id()));
+        let net_path = net_path.to_str().unwrap();
+        std::fs::write(net_path, r#"{"transitions": [{"label": "Clear open item", "inputs": ["p", "p"], "outputs": []}], "initial": {"p": 2}, "final": {}}"#).unwrap();
+        assert_eq!(generator::load_petri_net(net_path).err().unwrap().to_string(), "Duplicate place of a transition: p");
+        std::fs::write(net_path, r#"{"transitions": [{"label": "Clear open item", "inputs": ["p"], "outputs": [], "weight": 0}], "initial": {"p": 1}, "final": {}}"#).unwrap();
+        assert_eq!(generator::load_petri_net(net_path).err().unwrap().to_string(), "Invalid weight (must be positive): 0");
+        let _ = std::fs::remove_file(net_path);
+
+        // A loop which never reaches the final marking is an error (not a cut-off trace)
+        let unbounded = generator::Model::PetriNet(generator::PetriNet {
+            transitions: vec![transition(0, &["p"], &["p"])],
+            initial: [("p".to_string(), 1)].into_iter().collect(),
+            final_marking: [("end".to_string(), 1)].into_iter().collect(),
+        });
+        assert!(generator::generate(&unbounded, &generator_config(1, 1)).is_err());
+        assert!(generator::check_activity("Clear open itme").is_err());
+    }
+
+    #[test]
+    fn test_distribution_parsing() {
+        assert_eq!(generator::Distribution::parse("exp:60").unwrap(), generator::Distribution::Exponential(60.0));
+        assert_eq!(generator::Distribution::parse("uniform:1:2").unwrap(), generator::Distribution::Uniform(1.0, 2.0));
+        assert!(generator::Distribution::parse("uniform:2:1").is_err());
+        assert!(generator::Distribution::parse("poisson:1").is_err());
+        assert!(generator::Distribution::parse("exp:x").is_err());
+    }
+
+    #[test]
+    fn test_simulation_what_if() {
+        // Generated cases take a day per step; 3 of 4 cases go through the payment block (26, 5)
+        let cases = generate_test_cases_from_variants(vec![(vec![8, 26, 5, 4], 3), (vec![8, 4], 1)]);
+        let traces = traces::build_traces(cases).unwrap();
+        let mut overrides = simulation::Overrides::default();
+        overrides.duration_factors.insert(4, 0.5);
+        overrides.routing_factors.insert(26, 0.0);
+
+        let result = simulation::what_if(&traces, 2, &overrides, 1000, 1, 10);
+        assert_eq!(result["as_is"]["cases"], 4);
+        assert_eq!(result["as_is"]["throughput_seconds"]["mean"], 2.5 * 86400.0);
+        // Nothing was blocked, and clearing takes half a day
+        assert_eq!(result["what_if"]["variants"].as_array().unwrap().len(), 1);
+        assert_eq!(result["what_if"]["variants"][0]["share"], 1.0);
+        assert_eq!(result["what_if"]["throughput_seconds"]["max"], 0.5 * 86400.0);
+
+        let baseline_share = result["simulated_baseline"]["variants"][0]["share"].as_f64().unwrap();
+        assert!((baseline_share - 0.75).abs() < 0.05);
+        assert!(simulation::parse_override("Clear open item=-1").is_err());
+        assert_eq!(simulation::parse_override("Clear open item=0.5").unwrap(), (4, 0.5));
+
+        // An activity starting some cases (8) and following another one in others takes a day there, too
+        let traces = traces::build_traces(generate_test_cases_from_variants(vec![(vec![8, 4], 2), (vec![9, 8, 4], 2)])).unwrap();
+        for (variant, seconds) in simulation::Model::discover(&traces, 2).simulate(200, 1) {
+            assert_eq!(seconds, (variant.len() - 1) as f64 * 86400.0);
+        }
+        // Without history, the routing may lead to 9 (never seen after the start of a case) anywhere
+        assert!(simulation::Model::discover(&traces, 0).simulate(200, 1).iter().any(|(variant, _)| variant[1..].contains(&9)));
+    }
+
+    #[test]
+    fn test_sqlite_ingestion_and_persistence() {
+        let db_path = std::env::temp_dir().join(format!("procure2pay_test_{}.db", std::process::id()));
+        let db_path = db_path.to_str().unwrap();
+        let _ = std::fs::remove_file(db_path);
+        {
+            let connection = rusqlite::Connection::open(db_path).unwrap();
+            connection.execute_batch(
+                "CREATE TABLE events (case_id INTEGER, activity TEXT, timestamp TEXT);
+                 INSERT INTO events VALUES (1, 'Create FI invoice by vendor', '2024-08-17 10:00:00.000');
+                 INSERT INTO events VALUES (1, 'Clear open item', '2024-08-18 10:00:00');
+                 INSERT INTO events VALUES (2, 'Create FI invoice by vendor', '2024-08-17T11:00:00');
+                 INSERT INTO events VALUES (3, 'Create FI invoice by vendor', '2024-08-17 12:00:00');
+                 INSERT INTO events VALUES (3, 'Clear open item', '2024-08-19 12:00:00');",
+            ).unwrap();
+        }
+        let cases = sqlite::read_events(db_path, sqlite::DEFAULT_QUERY, &Zone::utc()).unwrap();
+        assert_eq!(cases.len(), 5);
+        assert_eq!(cases[0], ("1".to_string(), parse_date("2024-08-17") + chrono::Duration::hours(10), "Create FI invoice by vendor".to_string()));
+        assert!(sqlite::read_events(db_path, "SELECT case_id FROM events", &Zone::utc()).is_err());
+
+        let variants = process_cases(cases.clone());
+        let assignments = traces::assign_variants(&traces::build_traces(cases).unwrap(), &variants);
+        assert_eq!(assignments, vec![("1".to_string(), 1), ("2".to_string(), 2), ("3".to_string(), 1)]);
+
+        let run = sqlite::Run { source: "test", engine: "parallel", events: 5, duration_ms: 0 };
+        let run_id = sqlite::persist(db_path, &run, &variants, &assignments).unwrap();
+        let connection = rusqlite::Connection::open(db_path).unwrap();
+        let (cases_count, variants_count): (i64, i64) = connection
+            .query_row("SELECT cases, variants FROM runs WHERE run_id = ?1", [run_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!((cases_count, variants_count), (3, 2));
+        let activities: String = connection
+            .query_row("SELECT v.activities FROM case_variants c JOIN variants v USING (run_id, variant_id) WHERE c.case_id = '1'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(activities, r#"["Create FI invoice by vendor","Clear open item"]"#);
+        let _ = std::fs::remove_file(db_path);
+    }
+
+    #[test]
+    fn test_parquet_input_and_output() {
+        use arrow_array::builder::StringDictionaryBuilder;
+        use arrow_array::types::Int32Type;
+        use arrow_array::{ArrayRef, Int64Array, RecordBatch, TimestampMillisecondArray};
+        use std::sync::Arc;
+
+        let cases = generate_test_cases_from_variants(vec![(vec![0, 3, 6, 8, 4], 5), (vec![9, 7, 4], 3)]);
+        let mut activity_names = StringDictionaryBuilder::<Int32Type>::new();
+        for (_, _, activity) in &cases {
+            activity_names.append_value(activity);
+        }
+        let columns: Vec<(&str, ArrayRef)> = vec![
+            ("case_id", Arc::new(Int64Array::from_iter_values(cases.iter().map(|c| c.0.parse::<i64>().unwrap())))),
+            ("activity", Arc::new(activity_names.finish())),
+            ("timestamp", Arc::new(TimestampMillisecondArray::from_iter_values(cases.iter().map(|c| c.1.and_utc().timestamp_millis())))),
+        ];
+        let input_path = std::env::temp_dir().join(format!("procure2pay_test_{}_input.parquet", std::process::id()));
+        let input_path = input_path.to_str().unwrap();
+        let batch = RecordBatch::try_from_iter(columns).unwrap();
+        let mut writer = parquet::arrow::ArrowWriter::try_new(std::fs::File::create(input_path).unwrap(), batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let log = columnar::read_events(input_path, &columnar::Columns { case_id: "case_id", activity: "activity", timestamp: "timestamp", zone: Zone::utc() }).unwrap();
+        assert_eq!(log.case_ids.len(), 8);
+        assert_eq!(log.decode(), cases);
+        let variants = parallel::process_encoded(log.events);
+        assert_eq!(variants, vec![(vec![0, 3, 6, 8, 4], 5), (vec![9, 7, 4], 3)]);
+        assert!(columnar::read_events(input_path, &columnar::Columns { case_id: "case", activity: "activity", timestamp: "timestamp", zone: Zone::utc() }).is_err());
+
+        // Null case ids and null dictionary keys are errors, not case "0" or the first activity
+        let nulls_path = std::env::temp_dir().join(format!("procure2pay_test_{}_nulls.parquet", std::process::id()));
+        let nulls_path = nulls_path.to_str().unwrap();
+        for (case_ids, activity) in [(vec![Some(1), None], Some("Clear open item")), (vec![Some(1), Some(2)], None)] {
+            let mut activity_names = StringDictionaryBuilder::<Int32Type>::new();
+            activity_names.append_value("Clear open item");
+            activity_names.append_option(activity);
+            let columns: Vec<(&str, ArrayRef)> = vec![
+                ("case_id", Arc::new(Int64Array::from(case_ids))),
+                ("activity", Arc::new(activity_names.finish())),
+                ("timestamp", Arc::new(TimestampMillisecondArray::from_iter_values([0, 0]))),
+            ];
+            let batch = RecordBatch::try_from_iter(columns).unwrap();
+            let mut writer = parquet::arrow::ArrowWriter::try_new(std::fs::File::create(nulls_path).unwrap(), batch.schema(), None).unwrap();
+            writer.write(&batch).unwrap();
+            writer.close().unwrap();
+            let result = columnar::read_events(nulls_path, &columnar::Columns { case_id: "case_id", activity: "activity", timestamp: "timestamp", zone: Zone::utc() });
+            assert!(result.is_err_and(|err| err.to_string().contains("in row 1")));
+        }
+        let _ = std::fs::remove_file(nulls_path);
+
+        let output_path = std::env::temp_dir().join(format!("procure2pay_test_{}_variants.parquet", std::process::id()));
+        let output_path = output_path.to_str().unwrap();
+        columnar::write_variants(output_path, &variants).unwrap();
+        let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(output_path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<RecordBatch> = reader.map(|batch| batch.unwrap()).collect();
+        assert_eq!(batches[0].num_rows(), 2);
+        assert_eq!(batches[0].schema().field(2).name(), "activities");
+        let _ = std::fs::remove_file(input_path);
+        let _ = std::fs::remove_file(output_path);
+    }
+
+    #[test]
+    fn test_case_to_variant_assignments() {
+        let mut cases = generate_test_cases_from_variants(vec![(vec![0, 3, 6, 8, 4], 300), (vec![9, 7, 4], 200), (vec![0, 4], 1)]);
+        let expected = process_cases(cases.clone());
+        cases.reverse();
+        let variant_cases = process_cases_with_case_ids(cases);
+        assert_eq!(assignments::counts(&variant_cases), expected);
+        // generate_test_cases_from_variants() numbers the cases variant by variant
+        assert_eq!(variant_cases[2], (vec![0, 4], vec!["501".to_string()]));
+        let mut expected_ids: Vec<String> = (1..=300).map(|id| id.to_string()).collect();
+        expected_ids.sort();
+        assert_eq!(variant_cases[0].1, expected_ids);
+
+        let case_assignments = assignments::assign(&variant_cases);
+        assert_eq!(case_assignments.len(), 501);
+        assert!(case_assignments.contains(&("42".to_string(), 1)) && case_assignments.contains(&("301".to_string(), 2)));
+        let json = assignments::to_json(&variant_cases);
+        assert_eq!(json[1]["variant_id"], 2);
+        assert_eq!(json[1]["count"], 200);
+        assert_eq!(json[2]["activities"], serde_json::json!([activities::num_to_str(0), activities::num_to_str(4)]));
+        assert!(assignments::write("assignments.txt", &variant_cases).is_err());
+    }
+
+    #[test]
+    fn test_incremental_state() {
+        let state_path = std::env::temp_dir().join(format!("procure2pay_test_{}_state.json", std::process::id()));
+        let state_path = state_path.to_str().unwrap();
+        let _ = std::fs::remove_file(state_path);
+        let end = activities::str_to_num("Clear open item");
+        let cases = generate_test_cases_from_variants(vec![(vec![0, 3, 6, 8, 4], 5), (vec![9, 7, 4], 3), (vec![9, 7], 2)]);
+        let expected = process_cases(cases.clone());
+
+        // Splits the events into two nightly batches (no case ends within the first one)
+        let (first, second): (Vec<_>, Vec<_>) = cases.into_iter().partition(|event| event.1 < parse_date("2024-08-20"));
+        let mut state = incremental::State::load_or_new(state_path, end).unwrap();
+        let update = state.update(first).unwrap();
+        assert_eq!((update.finalized, update.open_cases), (0, 6));
+        state.save(state_path).unwrap();
+
+        let mut state = incremental::State::load_or_new(state_path, end).unwrap();
+        let late = ("6".to_string(), parse_date("2024-08-21"), "Clear open item".to_string());
+        let update = state.update(second.into_iter().chain([late.clone()]).collect()).unwrap();
+        assert_eq!((update.finalized, update.open_cases), (8, 2));
+        let update = state.update(vec![late]).unwrap();
+        assert_eq!((update.late_events, update.late_cases), (1, vec!["6".to_string()]));
+        // The cases of the last variant never reach the end activity
+        assert_eq!(state.top_variants(), expected[..2].to_vec());
+        assert!(incremental::State::load_or_new(state_path, 0).is_err());
+
+        // Activities registered by a previous run (e.g. by a label mapping) are registered again
+        let variants = r#"[[["Incremental test category", "Clear open item"], 2]]"#;
+        let json = format!(r#"{{"version": 1, "end_activity": "Clear open item", "open": {{}}, "closed": [], "variants": {}}}"#, variants);
+        std::fs::write(state_path, json).unwrap();
+        let state = incremental::State::load_or_new(state_path, end).unwrap();
+        let category = activities::try_str_to_num("Incremental test category").unwrap();
+        assert!(category >= activities::FIRST_REGISTERED);
+        assert_eq!(state.top_variants(), vec![(vec![category, end], 2)]);
+        let _ = std::fs::remove_file(state_path);
+    }
+
+    #[test]
+    fn test_stream_replay() {
+        let utc = Zone::utc();
+        assert_eq!(stream::parse_line("case_id;activity;timestamp", stream::Format::Csv, &utc).unwrap(), None);
+        let event = stream::parse_line(r#"{"case_id": 7, "activity": "Clear open item", "timestamp": "2024-08-18T00:00:00"}"#, stream::Format::Json, &utc).unwrap();
+        assert_eq!(event, Some(("7".to_string(), parse_date("2024-08-18"), "Clear open item".to_string())));
+        assert!(stream::parse_line("7;Clear open item;yesterday", stream::Format::Csv, &utc).is_err());
+        // Local times of the source zone are converted to UTC
+        let event = stream::parse_line("7;Clear open item;2024-08-18 02:00:00", stream::Format::Csv, &Zone::parse("Europe/Berlin").unwrap()).unwrap();
+        assert_eq!(event.unwrap().1, parse_date("2024-08-18"));
+
+        // Replays a recorded log (ordered by time, as it would arrive) with an unknown activity
+        let mut cases = generate_test_cases_from_variants(vec![(vec![0, 3, 6, 8, 4], 5), (vec![9, 7, 4], 3), (vec![9, 7], 2)]);
+        let expected = process_cases(cases.clone());
+        cases.sort_by_key(|event| event.1);
+        let mut recorded = Vec::new();
+        generator::write_csv(&cases, &mut recorded).unwrap();
+        recorded.extend_from_slice(b"11;Unknown activity;2024-08-18 00:00:00\n");
+
+        let config = stream::Config { format: stream::Format::Csv, top: 10, with_names: false, case_timeout: Some(chrono::Duration::days(2)), zone: utc, display_zone: Zone::parse("Europe/Berlin").ok() };
+        let mut monitor = stream::Monitor::new(incremental::State::new(activities::str_to_num("Clear open item")), config);
+        let mut output = Vec::new();
+        let source = stream::Source::Reader(Box::new(std::io::Cursor::new(recorded)));
+        stream::run(source, &mut monitor, std::time::Duration::from_secs(3600), &mut output, |_| Ok(())).unwrap();
+
+        let snapshot: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(snapshot["events"], 38);
+        assert_eq!(snapshot["invalid_lines"], 1);
+        assert_eq!(snapshot["finalized_cases"], 8);
+        // The cases of the last variant never reach the end activity, but time out (idle for more than two days)
+        assert_eq!((snapshot["timed_out_cases"].as_u64(), snapshot["open_cases"].as_u64()), (Some(2), Some(0)));
+        assert_eq!(snapshot["top"], serde_json::json!(expected));
+        assert!(snapshot["watermark"].as_str().unwrap().ends_with(" +02:00"));
+    }
+
+    #[test]
+    fn test_out_of_core() {
+        let cases = generate_test_cases_from_variants(vec![(vec![0, 3, 6, 8, 4], 50), (vec![9, 7, 4], 30), (vec![9, 7], 20), (vec![1, 4], 1)]);
+        let expected = process_cases(cases.clone());
+        let log_path = std::env::temp_dir().join(format!("procure2pay_test_{}_out_of_core.csv", std::process::id()));
+        generator::write_csv(&cases, &mut std::fs::File::create(&log_path).unwrap()).unwrap();
+
+        let spill_dir = std::env::temp_dir().join(format!("procure2pay_test_{}_spill", std::process::id()));
+        for partitions in [1, 7, 500] {
+            assert_eq!(out_of_core::process_file(log_path.to_str().unwrap(), partitions, &spill_dir, &Zone::utc()).unwrap(), expected);
+        }
+        // The partitions are removed after processing
+        assert_eq!(std::fs::read_dir(&spill_dir).unwrap().count(), 0);
+        let _ = std::fs::remove_dir(&spill_dir);
+        let _ = std::fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn test_interned_engine_matches_gold() {
+        let mut cases = generate_test_cases_from_variants(vec![
+            (vec![0, 3, 6, 8, 4], 400), (vec![9, 7, 4], 250), (vec![9, 7, 7, 4], 120), (vec![1, 0, 6, 8, 4], 3), (vec![2], 1),
+        ]);
+        cases.reverse();
+        let expected = crate::sequential::process_cases(cases.clone()).unwrap();
+        assert_eq!(crate::interned::process_cases(cases).unwrap(), expected);
+        assert_eq!(expected[2], (vec![1, 0, 6, 8, 4], 3));
+        assert!(crate::interned::process_cases(Vec::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_interned_engine_on_parquet() {
+        use arrow_array::{ArrayRef, Int64Array, RecordBatch, StringArray, TimestampMillisecondArray};
+        use std::sync::Arc;
+
+        // The interned engine works on the decoded events of a Parquet log (not on the encoded ones)
+        let cases = generate_test_cases_from_variants(vec![(vec![0, 4], 2), (vec![9, 7, 4], 1)]);
+        let columns: Vec<(&str, ArrayRef)> = vec![
+            ("case_id", Arc::new(Int64Array::from_iter_values(cases.iter().map(|c| c.0.parse::<i64>().unwrap())))),
+            ("activity", Arc::new(StringArray::from_iter_values(cases.iter().map(|c| &c.2)))),
+            ("timestamp", Arc::new(TimestampMillisecondArray::from_iter_values(cases.iter().map(|c| c.1.and_utc().timestamp_millis())))),
+        ];
+        let parquet_path = std::env::temp_dir().join(format!("procure2pay_test_{}_interned.parquet", std::process::id()));
+        let parquet_path = parquet_path.to_str().unwrap();
+        let batch = RecordBatch::try_from_iter(columns).unwrap();
+        let mut writer = parquet::arrow::ArrowWriter::try_new(std::fs::File::create(parquet_path).unwrap(), batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let columns = columnar::Columns { case_id: "case_id", activity: "activity", timestamp: "timestamp", zone: Zone::utc() };
+        let log = columnar::read_events(parquet_path, &columns).unwrap();
+        let expected = crate::sequential::process_cases(log.decode()).unwrap();
+        assert_eq!(expected, vec![(vec![0, 4], 2), (vec![9, 7, 4], 1)]);
+        assert_eq!(crate::interned::process_cases(log.decode()).unwrap(), expected);
+        assert_eq!(parallel::process_encoded(log.events), expected);
+        let _ = std::fs::remove_file(parquet_path);
+    }
+
+    #[test]
+    fn test_html_report() {
+        let cases = generate_test_cases_from_variants(vec![(vec![0, 3, 6, 8, 4], 30), (vec![9, 7, 4], 20), (vec![9, 7, 4, 7], 1)]);
+        let events = cases.len();
+        let variants = process_cases(cases.clone());
+        let html = report::render("<log> & co", events, &traces::build_traces(cases).unwrap(), &variants, None);
+
+        assert!(html.contains("<title>&lt;log&gt; &amp; co</title>"));
+        assert!(html.contains(&format!("<th>Events</th><td class=\"number\">{}</td>", events)));
+        assert!(html.contains("<th>Cases</th><td class=\"number\">51</td>"));
+        // The last variant completes the coverage
+        assert!(html.contains("<td class=\"number\">2.0 %</td><td class=\"number\">100.0 %</td>"));
+        // One directly-follows graph and histograms of all cases and of each variant
+        assert_eq!(html.matches("<svg").count(), 1 + 1 + 3);
+        // Backward edge (Post invoice in FI -> Clear open item -> Post invoice in FI)
+        assert!(html.contains(&format!("<title>{} &rarr; {}: 1</title>", activities::num_to_str(4), activities::num_to_str(7))));
+        // Self-contained: no scripts or external resources
+        assert!(!html.contains("<script") && !html.contains("src=") && !html.contains("href="));
+    }
+
+    // Sends one HTTP request, returns the status code and the JSON body
+    fn http_request(address: std::net::SocketAddr, method: &str, url: &str, body: &str) -> (u16, serde_json::Value) {
+        use std::io::{Read, Write};
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        write!(stream, "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}", method, url, body.len(), body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head[9..12].parse().unwrap(), serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn test_http_api() {
+        let cases = generate_test_cases_from_variants(vec![(vec![0, 3, 6, 8, 4], 30), (vec![9, 7, 4], 20), (vec![0, 26, 5, 4], 5)]);
+        let data_dir = std::env::temp_dir().join(format!("procure2pay_test_{}_serve", std::process::id()));
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let mut log = Vec::new();
+        generator::write_csv(&cases, &mut log).unwrap();
+        std::fs::write(data_dir.join("log.csv"), &log).unwrap();
+
+        let http = server::bind("127.0.0.1:0").unwrap();
+        let address = http.server_addr().to_ip().unwrap();
+        let mut api = server::Api::new(&data_dir);
+        std::thread::spawn(move || server::run(&http, &mut api).expect("Failed to serve"));
+
+        assert_eq!(http_request(address, "POST", "/logs?name=uploaded", std::str::from_utf8(&log).unwrap()).0, 201);
+        assert_eq!(http_request(address, "POST", "/logs?name=loaded&path=log.csv", "").0, 201);
+        assert_eq!(http_request(address, "POST", "/logs?name=outside&path=../log.csv", "").0, 400);
+        let (status, variants) = http_request(address, "GET", "/logs/loaded/variants?engine=gold&top=2", "");
+        assert_eq!((status, variants), (200, serde_json::json!(process_cases(cases.clone())[..2])));
+
+        // Cases without "Set payment block" starting from the third day (the first two cases of each variant are dropped)
+        let (_, variants) = http_request(address, "GET", "/logs/uploaded/variants?with_names=true&excludes=Set+payment+block&from=2024-08-20", "");
+        assert_eq!(variants.as_array().unwrap().len(), 2);
+        assert_eq!(variants[1], serde_json::json!([[activities::num_to_str(9), activities::num_to_str(7), activities::num_to_str(4)], 18]));
+        let (_, stats) = http_request(address, "GET", "/logs/uploaded/stats?contains=Remove%20payment%20block", "");
+        assert_eq!((stats["cases"].as_u64(), stats["variants"].as_u64()), (Some(5), Some(1)));
+        assert_eq!(stats["first_event"], "2024-08-18 00:00:00");
+        // Local times of the log, also those of the filters and the statistics
+        assert_eq!(http_request(address, "POST", "/logs?name=zoned&path=log.csv&tz=%2B02:00", "").0, 201);
+        let (_, stats) = http_request(address, "GET", "/logs/zoned/stats?contains=Remove%20payment%20block&from=2024-08-20", "");
+        assert_eq!((stats["cases"].as_u64(), stats["first_event"].as_str()), (Some(3), Some("2024-08-20 00:00:00 +02:00")));
+        let (_, dfg) = http_request(address, "GET", "/logs/uploaded/dfg", "");
+        assert_eq!(dfg["start"], serde_json::json!([{"activity": activities::num_to_str(0), "count": 35}, {"activity": activities::num_to_str(9), "count": 20}]));
+
+        assert_eq!(http_request(address, "GET", "/logs/uploaded/variants?engine=fast", "").0, 400);
+        assert_eq!(http_request(address, "DELETE", "/logs/uploaded", "").0, 200);
+        assert_eq!(http_request(address, "GET", "/logs/uploaded/dfg", "").0, 404);
+        assert!(filters::Filters::parse(&[("from".to_string(), "yesterday".to_string())], &Zone::utc()).is_err());
+        let from = filters::Filters::parse(&[("from".to_string(), "2024-08-20".to_string())], &Zone::parse("+02:00").unwrap()).unwrap().from;
+        assert_eq!(from, Some(parse_date("2024-08-19") + chrono::Duration::hours(22)));
+        let _ = std::fs::remove_dir_all(&data_dir);
+    }
+
+    #[test]
+    fn test_explorer_model() {
+        use ratatui::crossterm::event::KeyCode;
+        let cases = generate_test_cases_from_variants(vec![(vec![0, 3, 6, 8, 4], 30), (vec![9, 7, 4], 20), (vec![0, 26, 5, 4], 5)]);
+        let mut explorer = explore::Explorer::new(cases, Zone::parse("+02:00").unwrap(), None).unwrap();
+        assert_eq!(explorer.names[1], (vec![activities::num_to_str(9).to_string(), activities::num_to_str(7).to_string(), activities::num_to_str(4).to_string()], 20));
+
+        // Expands the second variant: one day between its activities (see generate_test_cases_from_variants)
+        explorer.on_key(KeyCode::Down);
+        explorer.on_key(KeyCode::Enter);
+        assert_eq!(explorer.view, explore::View::Details);
+        let details = explorer.details();
+        assert_eq!((details[2].1, details[2].2), (2.0 * 86400.0, 86400.0));
+        assert_eq!(explore::format_duration(details[2].1), "2d 0h");
+
+        // Its cases (31..50), scrolled to the end
+        explorer.on_key(KeyCode::Char('c'));
+        explorer.on_key(KeyCode::End);
+        assert_eq!((explorer.view, explorer.selected_case), (explore::View::Cases, 19));
+        assert_eq!(explorer.selected_traces()[0].case_id, "31");
+        explorer.on_key(KeyCode::Esc);
+
+        // Typed filter, then an invalid one (kept filters), then reset
+        explorer.on_key(KeyCode::Char('f'));
+        "contains=Set payment block".chars().for_each(|c| explorer.on_key(KeyCode::Char(c)));
+        explorer.on_key(KeyCode::Enter);
+        assert_eq!((explorer.variants.len(), explorer.variants[0].1.len()), (1, 5));
+        explorer.on_key(KeyCode::Char('f'));
+        "from=someday".chars().for_each(|c| explorer.on_key(KeyCode::Char(c)));
+        explorer.on_key(KeyCode::Enter);
+        assert!(explorer.message.starts_with("Invalid time"));
+        assert_eq!(explorer.filter_parameters(), vec![("contains".to_string(), "Set payment block".to_string())]);
+        // A time of the source zone, kept with its offset (so adding another filter parses it to the same time)
+        explorer.on_key(KeyCode::Char('f'));
+        "from=2024-08-20".chars().for_each(|c| explorer.on_key(KeyCode::Char(c)));
+        explorer.on_key(KeyCode::Enter);
+        assert_eq!(explorer.filter_parameters()[1], ("from".to_string(), "2024-08-20 00:00:00 +02:00".to_string()));
+        explorer.on_key(KeyCode::Char('f'));
+        "excludes=Clear open item".chars().for_each(|c| explorer.on_key(KeyCode::Char(c)));
+        explorer.on_key(KeyCode::Enter);
+        assert_eq!(explorer.filters.from, Some(parse_date("2024-08-19") + chrono::Duration::hours(22)));
+        assert_eq!(explorer.display(parse_date("2024-08-20")), "2024-08-20 00:00:00");
+        explorer.on_key(KeyCode::Char('r'));
+        assert_eq!(explorer.variants.len(), 3);
+        explorer.on_key(KeyCode::Char('q'));
+        assert!(explorer.quit);
+    }
+
+    #[test]
+    fn test_config_profiles() {
+        let config = config::parse(r#"
+            profile = "nightly"
+            [profiles.base]
+            input = { path = "log.parquet", format = "parquet", case_column = "case" }
+            engine = "gold"
+            output = { top = 5 }
+            [profiles.base.activities]
+            "Post invoice in MM" = "Post invoice"
+            [profiles.nightly]
+            extends = "base"
+            dedup = "first-occurrence"
+            filters = { contains = ["Set payment block"] }
+            output = { with_names = true, report = "nightly.html" }
+            [profiles.loop]
+            extends = "loop"
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.input.path.as_deref(), Some("log.parquet"));
+        assert_eq!(profile.to_args().unwrap(), [
+            "--case-column=case", "--dedup=first-occurrence", "--top=5", "--report=nightly.html", "--parquet", "--gold",
+            "--with-names", "--rename=Post invoice in MM=Post invoice", "--contains=Set payment block",
+        ]);
+        assert!(config.profile(Some("loop")).is_err());
+        assert!(config.profile(Some("o2c")).is_err());
+        assert!(config::parse("[profiles.base]\nengine = \"fast\"").unwrap().profile(None).unwrap().to_args().is_err());
+        assert!(config::parse("[profiles.base]\nengnie = \"gold\"").is_err());
+        assert!(config::parse(&std::fs::read_to_string("procure2pay.toml").unwrap()).unwrap().profile(Some("p2p-parquet")).is_ok());
+
+        // Renames and the first-occurrence dedup policy
+        let mut events = generate_test_cases_from_variants(vec![(vec![9, 7, 4, 7], 2)]);
+        let mapping = labels::Mapping { rename: [(activities::num_to_str(9).to_string(), activities::num_to_str(1).to_string())].into(), ..Default::default() };
+        mapping.apply(&mut events, labels::Level::Fine).unwrap();
+        assert_eq!(process_cases(events.clone()), vec![(vec![1, 7, 4, 7], 2)]);
+        assert_eq!(process_cases(filters::first_occurrences(events)), vec![(vec![1, 7, 4], 2)]);
+    }
+
+    #[test]
+    fn test_label_levels() {
+        let mapping = labels::Mapping::parse(&std::fs::read_to_string("labels.toml").unwrap()).unwrap();
+        assert_eq!(mapping.map("Create MM invoice by vendor", labels::Level::Fine), "Create invoice by vendor");
+        assert_eq!(mapping.map("Post invoice in MM", labels::Level::Fine), "Post invoice in MM");
+        assert_eq!(mapping.map("Post invoice in MM", labels::Level::Coarse), "Post invoice");
+        assert_eq!(mapping.map("Change purchase order (currency)", labels::Level::Coarse), "Change PO");
+        assert_eq!(mapping.map("Change cash discount days 2", labels::Level::Coarse), "Change payment terms");
+        assert_eq!(mapping.map("Clear open item", labels::Level::Coarse), "Clear open item");
+        // Overlapping patterns: the category declared first wins (not the first in alphabetical order)
+        assert_eq!(mapping.map("Increase purchase order item quantity", labels::Level::Coarse), "Change PO quantity");
+        assert_eq!(mapping.map("Increase purchase order item price", labels::Level::Coarse), "Change PO price");
+        let reversed = labels::Mapping::parse("[categories]\n\"Z\" = [\"Post *\"]\n\"A\" = [\"Post invoice in MM\"]").unwrap();
+        assert_eq!(reversed.map("Post invoice in MM", labels::Level::Coarse), "Z");
+        assert!(labels::matches("Post * in *", "Post invoice in FI"));
+        assert!(!labels::matches("Post invoice in *", "Post invoice"));
+        assert!(!labels::matches("Change *item", "Change item text"));
+        assert!(labels::Mapping::parse("[rename]\n\"A\" = 1").is_err());
+        assert!(labels::parse_rename("Post invoice in MM").is_err());
+
+        // Post invoice in FI (7) and in MM (8), merged into a new activity at the coarse level
+        let events = generate_test_cases_from_variants(vec![(vec![9, 7, 4], 3), (vec![1, 8, 4], 2)]);
+        let mut fine = events.clone();
+        mapping.apply(&mut fine, labels::Level::Fine).unwrap();
+        let fine_variants = process_cases(fine);
+        assert_eq!(fine_variants.len(), 2);
+        assert!(fine_variants[0].0[0] >= activities::FIRST_REGISTERED);
+        let mut coarse = events;
+        mapping.apply(&mut coarse, labels::Level::Coarse).unwrap();
+        let coarse_variants = process_cases(coarse);
+        assert_eq!(coarse_variants.len(), 1);
+        assert_eq!(coarse_variants[0].1, 5);
+        assert_eq!(activities::num_to_str(coarse_variants[0].0[1]), "Post invoice");
+        assert_eq!(activities::try_str_to_num("Post invoice"), Some(coarse_variants[0].0[1]));
+    }
+
+    #[test]
+    fn test_timezones() {
+        let utc = |text: &str| NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").unwrap();
+        let berlin = Zone::parse("Europe/Berlin").unwrap();
+        assert_eq!(Zone::parse("UTC").unwrap(), Zone::utc());
+        assert_eq!(Zone::parse("+02:00").unwrap(), Zone::parse("+0200").unwrap());
+        assert!(Zone::parse("Mars/Olympus_Mons").is_err());
+        assert!(Zone::parse("+25:00").is_err());
+
+        // Offsets of the timestamps win over the zone of the source
+        assert_eq!(timezones::parse_timestamp("2024-06-01 12:00:00+02:00", &berlin).unwrap(), utc("2024-06-01 10:00:00"));
+        assert_eq!(timezones::parse_timestamp("2024-06-01T12:00:00Z", &berlin).unwrap(), utc("2024-06-01 12:00:00"));
+        assert_eq!(timezones::parse_timestamp("2024-06-01 12:00:00", &berlin).unwrap(), utc("2024-06-01 10:00:00"));
+        assert_eq!(timezones::parse_timestamp("2024-01-01 12:00:00", &berlin).unwrap(), utc("2024-01-01 11:00:00"));
+        assert!(timezones::parse_timestamp("2024-06-01 12:00", &berlin).is_err());
+        // DST gap (02:00 -> 03:00) and overlap (03:00 -> 02:00)
+        assert_eq!(timezones::parse_timestamp("2024-03-31 02:30:00", &berlin).unwrap(), utc("2024-03-31 01:30:00"));
+        assert_eq!(timezones::parse_timestamp("2024-10-27 02:30:00", &berlin).unwrap(), utc("2024-10-27 00:30:00"));
+        assert_eq!(berlin.format(utc("2024-10-27 01:30:00")), "2024-10-27 02:30:00 +01:00");
+
+        // A case over the end of DST takes an hour longer than its local times suggest, and the
+        // events of another plant (with offsets) are ordered by their UTC times
+        let log = "case_id;activity;timestamp\n\
+                   1;Create purchase order item;2024-10-27 01:30:00\n\
+                   1;Clear open item;2024-10-27 03:10:00\n\
+                   2;Create purchase order item;2024-10-27 02:40:00+01:00\n\
+                   2;Enter goods receipt;2024-10-27T02:20:00+02:00\n";
+        let events = csv_parser::parse_csv_from_in(log.as_bytes(), &berlin).unwrap();
+        let traces = traces::build_traces(events.clone()).unwrap();
+        assert_eq!(traces[0].timestamps[1] - traces[0].timestamps[0], chrono::Duration::minutes(160));
+        assert_eq!(traces[1].activities, vec![activities::str_to_num("Enter goods receipt"), activities::str_to_num("Create purchase order item")]);
+        assert_eq!(csv_parser::parse_csv_from(log.as_bytes()).unwrap()[0].1, utc("2024-10-27 01:30:00"));
+
+        let html = report::render("tz", events.len(), &traces, &process_cases(events), Some(&berlin));
+        assert!(html.contains("2024-10-27 01:30:00 +02:00") && html.contains("2024-10-27 03:10:00 +01:00"));
+    }
+
+    #[test]
+    fn test_group_by() {
+        let time = parse_date("2024-01-01");
+        let event = |case_id: &str, vendor: &str| {
+            ((case_id.to_string(), time, "Clear open item".to_string()), [("vendor".to_string(), vendor.to_string())].into())
+        };
+        // The earliest non-empty value of the case, the case table overrides it
+        let mut case_attributes = breakdown::from_events(&[event("1", ""), event("1", "V1"), event("2", "V2"), event("3", "V2")]);
+        assert_eq!(case_attributes["1"]["vendor"], "V1");
+        breakdown::join(&mut case_attributes, [("3".to_string(), [("vendor".to_string(), "V1".to_string())].into())].into());
+        assert_eq!(case_attributes["3"]["vendor"], "V1");
+        for (case_id, value) in [("1", "500"), ("2", "2500"), ("3", "25000"), ("4", "n/a")] {
+            case_attributes.entry(case_id.to_string()).or_default().insert("value".to_string(), value.to_string());
+        }
+
+        let cases = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let variants = vec![(vec![1, 2], cases(&["1", "3"])), (vec![1, 3], cases(&["2", "5"])), (vec![1, 4], cases(&["4"]))];
+
+        // V1 and V2 have disjoint variants, so the vendor determines the variant of the cases it's known for
+        let by_vendor = breakdown::Breakdown::compute(&variants, &case_attributes, "vendor", &[]);
+        assert_eq!(by_vendor.cases, 5);
+        assert_eq!(by_vendor.variants, vec![(vec![1, 2], 2), (vec![1, 3], 2), (vec![1, 4], 1)]);
+        let groups: Vec<_> = by_vendor.groups.iter().map(|group| (group.value.as_str(), group.cases, group.variants.clone())).collect();
+        assert_eq!(groups, vec![
+            (breakdown::MISSING, 2, vec![(vec![1, 3], 1), (vec![1, 4], 1)]),
+            ("V1", 2, vec![(vec![1, 2], 2)]),
+            ("V2", 1, vec![(vec![1, 3], 1)]),
+        ]);
+        // Group V1 only has a variant of 40% of all cases: log2(1 / 0.4)
+        assert!((by_vendor.groups[1].divergence - (1.0f64 / 0.4).log2()).abs() < 1e-9);
+        assert!(by_vendor.divergence > 0.0 && by_vendor.normalized_divergence <= 1.0);
+        let json = by_vendor.to_json(1, true);
+        assert_eq!(json["groups"][1]["top"], serde_json::json!([[[activities::num_to_str(1), activities::num_to_str(2)], 2]]));
+
+        let by_band = breakdown::Breakdown::compute(&variants, &case_attributes, "value", &breakdown::parse_bands("1000, 10000").unwrap());
+        let mut bands: Vec<&str> = by_band.groups.iter().map(|group| group.value.as_str()).collect();
+        bands.sort_unstable();
+        assert_eq!(bands, vec![breakdown::MISSING, "1000-10000", "<1000", ">=10000", "n/a (not a number)"]);
+        assert!(breakdown::parse_bands("10000,1000").is_err());
+        assert!(breakdown::parse_bands("1000,lots").is_err());
+
+        // The same behavior in all groups
+        let same = breakdown::Breakdown::compute(&[(vec![1, 2], cases(&["1", "2"]))], &case_attributes, "vendor", &[]);
+        assert_eq!((same.divergence, same.normalized_divergence), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_sequential_patterns() {
+        let variants = vec![(vec![1, 2, 3, 4], 3), (vec![1, 5, 2, 4], 2), (vec![2, 1, 4], 1)];
+        let miner = |max_gap: Option<usize>| patterns::Miner { min_support: 0.5, max_gap, min_length: 2, max_length: 4 };
+        let find = |mined: &[patterns::Pattern], activities: &[u8]| mined.iter().find(|pattern| pattern.activities == activities).map(|pattern| (pattern.support, pattern.variants));
+
+        let mined = miner(None).mine(&variants);
+        assert_eq!(find(&mined, &[1, 4]), Some((6, 3)));
+        assert_eq!(find(&mined, &[1, 2, 4]), Some((5, 2)));
+        assert_eq!(find(&mined, &[1, 2]), Some((5, 2)));
+        assert_eq!(find(&mined, &[1, 3]), Some((3, 1)));
+        assert_eq!(find(&mined, &[5, 2]), None); // 2 of 6 cases
+        // Shared by the most variants first
+        assert_eq!(mined[0].variants, 3);
+        assert!(mined.windows(2).all(|pair| pair[0].variants >= pair[1].variants));
+
+        // Adjacent activities only
+        let adjacent = miner(Some(0)).mine(&variants);
+        assert_eq!(find(&adjacent, &[1, 2]), Some((3, 1)));
+        assert_eq!(find(&adjacent, &[3, 4]), Some((3, 1)));
+        assert_eq!(find(&adjacent, &[2, 4]), None); // 2 of 6 cases
+        assert_eq!(find(&adjacent, &[1, 4]), None);
+        assert_eq!(find(&miner(Some(1)).mine(&variants), &[2, 4]), Some((6, 3)));
+        // A huge gap is no gap limit (no overflow of the window)
+        assert_eq!(miner(Some(usize::MAX)).mine(&variants), mined);
+
+        // Against a brute force search on a generated log
+        fn occurs(variant: &[u8], pattern: &[u8], max_gap: usize, from: usize, first: bool) -> bool {
+            let window = if first { variant.len() } else { (from + max_gap + 1).min(variant.len()) };
+            pattern.is_empty()
+                || (from..window).any(|i| variant[i] == pattern[0] && occurs(variant, &pattern[1..], max_gap, i + 1, false))
+        }
+        let log = generate_test_cases_from_variants(vec![
+            (vec![0, 3, 12, 1, 6, 8, 4], 5), (vec![0, 1, 6, 8, 4], 4), (vec![9, 7, 4], 3), (vec![0, 6, 1, 3, 8, 6, 4], 2),
+        ]);
+        let variants = process_cases(log);
+        for max_gap in [Some(0), Some(2), None] {
+            let mined = patterns::Miner { min_support: 0.2, max_gap, min_length: 1, max_length: 7 }.mine(&variants);
+            for pattern in &mined {
+                let with: Vec<usize> = variants
+                    .iter()
+                    .filter(|(variant, _)| occurs(variant, &pattern.activities, max_gap.unwrap_or(usize::MAX / 2), 0, true))
+                    .map(|(_, count)| *count)
+                    .collect();
+                assert_eq!((pattern.support, pattern.variants), (with.iter().sum(), with.len()), "{:?}", pattern.activities);
+                assert!(pattern.support >= 3);
+            }
+            // Every activity is in at least 20% of the cases (3 of 14)
+            assert_eq!(mined.iter().filter(|pattern| pattern.activities.len() == 1).count(), 9);
+            // and every frequent pair is found
+            for (a, b) in (0..13u8).flat_map(|a| (0..13u8).map(move |b| (a, b))) {
+                let support: usize = variants
+                    .iter()
+                    .filter(|(variant, _)| occurs(variant, &[a, b], max_gap.unwrap_or(usize::MAX / 2), 0, true))
+                    .map(|(_, count)| count)
+                    .sum();
+                assert_eq!(mined.iter().any(|pattern| pattern.activities == [a, b]), support >= 3);
+            }
+        }
+        let json = patterns::to_json(&mined, 1, true);
+        assert_eq!(json[0]["pattern"], serde_json::json!([activities::num_to_str(1), activities::num_to_str(4)]));
+    }
+
+    #[test]
+    fn test_anomalies() {
+        let mut events = generate_test_cases_from_variants(vec![(vec![0, 1, 4], 40), (vec![0, 1, 3, 1, 4], 1)]);
+        // The usual variant, but a month instead of two days
+        for (activity, day) in [(0, 0), (1, 1), (4, 30)] {
+            events.push(("slow".to_string(), parse_date("2024-08-18") + chrono::Duration::days(day), activities::num_to_str(activity).to_string()));
+        }
+        let traces = traces::build_traces(events).unwrap();
+        let anomalies = anomalies::score(&traces);
+        assert_eq!(anomalies.len(), 42);
+
+        // Rework: a rare variant with rare transitions and an activity twice
+        let rework = &anomalies[0];
+        assert_eq!(rework.trace.case_id, "41");
+        assert_eq!((rework.variant, rework.transitions, rework.activity_counts, rework.duration), (1.0, 1.0, 1.0, 0.0));
+        assert_eq!(rework.score, 0.75);
+        assert_eq!(rework.explanations.len(), 3);
+        assert_eq!(rework.explanations[2], format!("{} 2 times (1 of 42 cases with it at least 2 times)", activities::num_to_str(1)));
+
+        let slow = &anomalies[1];
+        assert_eq!(slow.trace.case_id, "slow");
+        assert_eq!(slow.duration, 1.0);
+        assert!(slow.variant < 0.01 && slow.transitions < 0.01);
+        assert_eq!(slow.explanations.len(), 1);
+        assert!(slow.explanations[0].starts_with("Extremely long throughput time: 30.0 days (median 2.0 days"));
+
+        // The usual cases: nothing to explain
+        assert!(anomalies[2..].iter().all(|anomaly| anomaly.explanations.is_empty() && anomaly.score < 0.01));
+
+        let json = anomalies::to_json(&anomalies, 2, Some(&Zone::parse("+02:00").unwrap()));
+        assert_eq!(json.as_array().unwrap().len(), 2);
+        assert_eq!(json[0]["trace"].as_array().unwrap().len(), 5);
+        assert_eq!(json[1]["trace"][2], serde_json::json!({"activity": activities::num_to_str(4), "timestamp": "2024-09-17 02:00:00 +02:00"}));
+        assert_eq!(json[1]["throughput_days"], 30.0);
+    }
+
+    #[test]
+    fn test_transition_system() {
+        // Each step of the generated cases takes a day
+        let traces = traces::build_traces(generate_test_cases_from_variants(vec![(vec![1, 2, 1, 4], 2), (vec![2, 1, 4], 1)])).unwrap();
+        let states = |abstraction: Abstraction| TransitionSystem::build(&traces, abstraction).states.keys().cloned().collect::<Vec<_>>();
+        assert_eq!(states(Abstraction::Prefix), vec![vec![], vec![1], vec![1, 2], vec![1, 2, 1], vec![1, 2, 1, 4], vec![2], vec![2, 1], vec![2, 1, 4]]);
+        assert_eq!(states(Abstraction::LastK(1)), vec![vec![], vec![1], vec![2], vec![4]]);
+        assert_eq!(states(Abstraction::LastK(2)), vec![vec![], vec![1], vec![1, 2], vec![1, 4], vec![2], vec![2, 1]]);
+        assert_eq!(states(Abstraction::Set), vec![vec![], vec![1], vec![1, 2], vec![1, 2, 4], vec![2]]);
+        assert_eq!(states(Abstraction::Multiset), vec![vec![], vec![1], vec![1, 1, 2], vec![1, 1, 2, 4], vec![1, 2], vec![1, 2, 4], vec![2]]);
+
+        let system = TransitionSystem::build(&traces, Abstraction::Set);
+        // {1, 2} is visited twice by the cases of 1, 2, 1, 4 (after 1 and 2 days) and once by
+        // 2, 1, 4 (after 1 day), and left to 1 (back to {1, 2}) or 4
+        let state = system.state_of(&[2, 1]).unwrap();
+        assert_eq!((state.visits, state.mean_elapsed(), state.mean_remaining()), (5, 86400.0 * 7.0 / 5.0, 86400.0 * 7.0 / 5.0));
+        assert_eq!(state.next_activity(), (Some(4), 3.0 / 5.0));
+        let self_loop = &system.transitions[&(vec![1, 2], 1, vec![1, 2])];
+        assert_eq!((self_loop.visits, self_loop.mean_seconds()), (2, 86400.0));
+        assert_eq!(system.states[&vec![]].visits, 3);
+        assert!(system.state_of(&[3]).is_none());
+
+        let json = system.to_json();
+        assert_eq!(json["abstraction"], "set");
+        assert_eq!(json["states"].as_array().unwrap().len(), 5);
+        assert_eq!(json["states"][3]["next"], serde_json::json!({"(end)": 3}));
+        assert_eq!(json["transitions"].as_array().unwrap().len(), system.transitions.len());
+        let dot = system.to_dot();
+        assert!(dot.contains("s0 [label=\"start\\nvisits 3, elapsed 0.0 d, remaining 2.7 d\"]"));
+        assert!(dot.contains(&format!("s2 -> s2 [label=\"{}\\n2 x 1.0 d\"", activities::num_to_str(1))));
+
+        assert_eq!(Abstraction::parse("last:3").unwrap(), Abstraction::LastK(3));
+        assert_eq!(Abstraction::parse("multiset").unwrap().name(), "multiset");
+        assert!(Abstraction::parse("last:0").is_err());
+        assert!(Abstraction::parse("suffix").is_err());
+    }
+
+    mod properties {
+        use super::parse_date;
+        use crate::{activities, interned, parallel, sequential};
+        use chrono::NaiveDateTime;
+        use proptest::prelude::*;
+
+        type Log = Vec<(String, NaiveDateTime, String)>;
+
+        // One case: mostly short, some single-event and some huge (spanning several chunks);
+        // events are (minutes since the case start, activity), few distinct minutes so that
+        // equal timestamps are common
+        fn case_events() -> impl Strategy<Value = Vec<(i64, u8)>> {
+            let event = (0..4i64, 0..10u8);
+            prop_oneof![
+                6 => prop::collection::vec(event.clone(), 2..8),
+                2 => prop::collection::vec(event.clone(), 1..2),
+                1 => prop::collection::vec(event, 100..400),
+            ]
+        }
+
+        // A shuffled log with duplicated events
+        fn log() -> impl Strategy<Value = Log> {
+            (prop::collection::vec(case_events(), 1..40), prop::collection::vec(any::<bool>(), 1..64))
+                .prop_map(|(cases, duplicates)| {
+                    let mut log = Vec::new();
+                    for (case, events) in cases.into_iter().enumerate() {
+                        for (minutes, activity) in events {
+                            let event = (case.to_string(), parse_date("2024-08-18") + chrono::Duration::minutes(minutes), activities::num_to_str(activity).to_string());
+                            if duplicates[log.len() % duplicates.len()] {
+                                log.push(event.clone());
+                            }
+                            log.push(event);
+                        }
+                    }
+                    log
+                })
+                .prop_shuffle()
+        }
+
+        // Variant counts in a canonical order (the engines order the variants with the same count arbitrarily)
+        fn canonical(mut variants: Vec<(Vec<u8>, usize)>) -> Vec<(Vec<u8>, usize)> {
+            variants.sort();
+            variants
+        }
+
+        fn encode(log: &Log) -> Vec<parallel::EncodedEvent> {
+            log.iter().map(|(case, timestamp, activity)| (case.parse().unwrap(), *timestamp, activities::str_to_num(activity))).collect()
+        }
+
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(64))]
+
+            #[test]
+            fn engines_agree(log in log(), threads in 1..9usize) {
+                let expected = canonical(sequential::process_cases(log.clone()).unwrap());
+                let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+                pool.install(|| {
+                    prop_assert_eq!(canonical(parallel::process_cases(log.clone()).unwrap()), expected.clone());
+                    prop_assert_eq!(canonical(parallel::process_encoded(encode(&log))), expected.clone());
+                    prop_assert_eq!(canonical(interned::process_cases(log.clone()).unwrap()), expected.clone());
+                    let with_case_ids = parallel::process_cases_with_case_ids(log.clone()).unwrap();
+                    prop_assert_eq!(canonical(crate::assignments::counts(&with_case_ids)), expected.clone());
+                    Ok(())
+                })?;
+            }
+
+            #[test]
+            fn split_points_never_split_a_case(case_lengths in prop::collection::vec(1..50usize, 0..30), threads in 1..17usize) {
+                let cases: Vec<usize> = case_lengths.iter().enumerate().flat_map(|(case, &len)| std::iter::repeat_n(case, len)).collect();
+                let splits = parallel::split_points(&cases, threads, |a, b| a == b);
+                prop_assert_eq!(splits.first().copied(), Some(0));
+                prop_assert_eq!(splits.last().copied(), Some(cases.len()));
+                for window in splits.windows(2) {
+                    // no empty chunks (unless the log itself is empty)
+                    prop_assert!(window[0] < window[1] || cases.is_empty());
+                }
+                for &split in splits.iter().skip(1).take(splits.len().saturating_sub(2)) {
+                    prop_assert!(cases[split - 1] != cases[split], "split {} inside a case: {:?}", split, splits);
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,131 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  timezones: Time zones of the timestamps in the logs
+//          * all timestamps of the events are UTC (as NaiveDateTime), so events of sources in
+//            different time zones are ordered correctly and durations are right across DST changes
+//          * timestamps with an offset (e.g. "2024-03-31 03:30:00+02:00" or "...Z") are converted
+//            with it, the others are local times of the zone of the source (UTC by default)
+//          * local times in a DST overlap are taken as the first of the two (before the clocks are
+//            set back), those in a DST gap as if the clocks hadn't been set forward yet
+//          * timestamps are displayed in a chosen zone (with its offset)
+//
+use chrono::{DateTime, Duration, FixedOffset, LocalResult, NaiveDateTime, Offset, TimeZone};
+use chrono_tz::Tz;
+use std::error::Error;
+
+const LOCAL_FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"];
+const OFFSET_FORMATS: [&str; 4] = ["%Y-%m-%d %H:%M:%S%.f%#z", "%Y-%m-%dT%H:%M:%S%.f%#z", "%Y-%m-%d %H:%M:%S%.f %#z", "%Y-%m-%dT%H:%M:%S%.f %#z"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zone {
+    // a constant offset from UTC (UTC itself is +00:00)
+    Fixed(FixedOffset),
+    // a zone of the tz database with its DST rules, e.g. Europe/Berlin
+    Named(Tz),
+}
+
+impl Default for Zone {
+    fn default() -> Zone {
+        Zone::utc()
+    }
+}
+
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    let (sign, digits) = match offset.as_bytes().first()? {
+        b'+' => (1, &offset[1..]),
+        b'-' => (-1, &offset[1..]),
+        _ => return None,
+    };
+    let digits = digits.replace(':', "");
+    if !digits.bytes().all(|digit| digit.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = match digits.len() {
+        2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (digits[..2].parse::<i32>().ok()?, digits[2..].parse::<i32>().ok()?),
+        _ => return None,
+    };
+    if minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+impl Zone {
+    pub fn utc() -> Zone {
+        Zone::Fixed(FixedOffset::east_opt(0).unwrap())
+    }
+
+    // UTC (or Z), an offset (+02:00, -0500, +01) or a zone name of the tz database
+    pub fn parse(name: &str) -> Result<Zone, Box<dyn Error>> {
+        let name = name.trim();
+        if name.eq_ignore_ascii_case("utc") || name == "Z" {
+            return Ok(Zone::utc());
+        }
+        if let Some(offset) = parse_offset(name) {
+            return Ok(Zone::Fixed(offset));
+        }
+        name.parse::<Tz>()
+            .map(Zone::Named)
+            .map_err(|_| format!("Unknown time zone (e.g. Europe/Berlin, UTC or +02:00): {}", name).into())
+    }
+
+    // The UTC time of a local time of the zone
+    pub fn to_utc(&self, local: NaiveDateTime) -> NaiveDateTime {
+        let tz = match self {
+            Zone::Fixed(offset) => return local - Duration::seconds(offset.local_minus_utc() as i64),
+            Zone::Named(tz) => tz,
+        };
+        match tz.from_local_datetime(&local) {
+            LocalResult::Single(time) => time.naive_utc(),
+            LocalResult::Ambiguous(earlier, _) => earlier.naive_utc(),
+            // In a DST gap: the offset of the day before (the transitions are months apart)
+            LocalResult::None => {
+                let before = tz.offset_from_utc_datetime(&(local - Duration::days(1))).fix();
+                local - Duration::seconds(before.local_minus_utc() as i64)
+            }
+        }
+    }
+
+    // The local time of the zone at a UTC time (e.g. to group by local days or months)
+    pub fn to_local(&self, utc: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            Zone::Fixed(offset) => offset.from_utc_datetime(&utc).naive_local(),
+            Zone::Named(tz) => tz.from_utc_datetime(&utc).naive_local(),
+        }
+    }
+
+    // The local time of the zone at a UTC time with the offset, e.g. "2024-03-31 03:30:00 +02:00"
+    pub fn format(&self, utc: NaiveDateTime) -> String {
+        match self {
+            Zone::Fixed(offset) => offset.from_utc_datetime(&utc).format("%Y-%m-%d %H:%M:%S%.f %:z").to_string(),
+            Zone::Named(tz) => tz.from_utc_datetime(&utc).format("%Y-%m-%d %H:%M:%S%.f %:z").to_string(),
+        }
+    }
+}
+
+// A UTC time as shown: in the display zone (with its offset), or as it is without one
+pub fn display(utc: NaiveDateTime, zone: Option<&Zone>) -> String {
+    zone.map_or_else(|| utc.to_string(), |zone| zone.format(utc))
+}
+
+// The UTC time of a timestamp: converted with its own offset if it has one, otherwise
+// a local time of the zone
+pub fn parse_timestamp(timestamp: &str, zone: &Zone) -> Result<NaiveDateTime, Box<dyn Error>> {
+    for format in LOCAL_FORMATS {
+        if let Ok(local) = NaiveDateTime::parse_from_str(timestamp, format) {
+            return Ok(zone.to_utc(local));
+        }
+    }
+    let with_offset = match timestamp.strip_suffix('Z') {
+        Some(utc) => format!("{}+00:00", utc),
+        None => timestamp.to_string(),
+    };
+    for format in OFFSET_FORMATS {
+        if let Ok(time) = DateTime::parse_from_str(&with_offset, format) {
+            return Ok(time.naive_utc());
+        }
+    }
+    Err(format!("Invalid timestamp: {}", timestamp).into())
+}
@@ -0,0 +1,61 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  traces: Per-case traces (the same ordering and de-duplication the engines use to compose
+//          variants, but keeping the case_id and timestamps for further analysis)
+//
+use crate::activities;
+use crate::csv_parser::Event;
+
+use chrono::NaiveDateTime;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::error::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub case_id: String,
+    // De-duplicated activity sequence, i.e. the variant of this case
+    pub activities: Vec<u8>,
+    // Timestamp of each activity above (first occurrence, if duplicated)
+    pub timestamps: Vec<NaiveDateTime>,
+}
+
+impl Trace {
+    // Throughput time of the case in days (first to last activity)
+    pub fn throughput_days(&self) -> f64 {
+        match (self.timestamps.first(), self.timestamps.last()) {
+            (Some(first), Some(last)) => (*last - *first).num_seconds() as f64 / 86400.0,
+            _ => 0.0,
+        }
+    }
+}
+
+// Builds traces of all cases, ordered by case_id (an error for an unknown activity)
+pub fn build_traces(raw_cases: Vec<Event>) -> Result<Vec<Trace>, Box<dyn Error>> {
+    let mut cases = raw_cases;
+    // Same ordering as in parallel.rs (case_id, timestamp, activity name)
+    cases.par_sort_unstable_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)).then_with(|| a.2.cmp(&b.2)));
+
+    let mut traces: Vec<Trace> = Vec::new();
+    for (case_id, timestamp, activity_name) in cases {
+        let activity = activities::check(&activity_name)?;
+        match traces.last_mut() {
+            Some(trace) if trace.case_id == case_id => {
+                if trace.activities.last() != Some(&activity) {
+                    trace.activities.push(activity);
+                    trace.timestamps.push(timestamp);
+                }
+            }
+            _ => traces.push(Trace { case_id, activities: vec![activity], timestamps: vec![timestamp] }),
+        }
+    }
+    Ok(traces)
+}
+
+// Assigns each case to its variant, variant_id being the 1-based position in the sorted
+// variants returned by the engines: (case_id, variant_id)
+pub fn assign_variants(traces: &[Trace], variants: &[(Vec<u8>, usize)]) -> Vec<(String, usize)> {
+    let ids: HashMap<&[u8], usize> = variants.iter().enumerate().map(|(i, (variant, _))| (variant.as_slice(), i + 1)).collect();
+    traces.iter().map(|trace| (trace.case_id.clone(), ids[trace.activities.as_slice()])).collect()
+}
@@ -0,0 +1,236 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  transition_system: Transition system of the traces with a selectable state abstraction
+//          * state of a case after each activity: its whole prefix, the last k activities, or
+//            the set or multiset of its activities so far (the start state: no activity yet)
+//          * states annotated with visits, mean elapsed and remaining times and next activities,
+//            transitions (labeled by the activity) with visits and mean times (performance overlay)
+//          * exported as JSON or DOT; the basis of the predictions (see prediction.rs)
+//
+use crate::activities;
+use crate::traces::Trace;
+
+use chrono::NaiveDateTime;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Abstraction {
+    Prefix,
+    LastK(usize),
+    Set,
+    Multiset,
+}
+
+// Annotation of one state: when the cases got here, how long it took them to finish from here
+// and what came next
+#[derive(Debug, Default)]
+pub struct StateStats {
+    pub visits: usize,
+    elapsed_sum: f64,
+    remaining_sum: f64,
+    // next activity (None = the case ended here) -> count
+    pub next: HashMap<Option<u8>, usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct TransitionStats {
+    pub visits: usize,
+    seconds_sum: f64,
+}
+
+pub struct TransitionSystem {
+    pub abstraction: Abstraction,
+    pub states: BTreeMap<Vec<u8>, StateStats>,
+    // (from state, activity, to state)
+    pub transitions: BTreeMap<(Vec<u8>, u8, Vec<u8>), TransitionStats>,
+}
+
+fn seconds(from: NaiveDateTime, to: NaiveDateTime) -> f64 {
+    (to - from).num_milliseconds() as f64 / 1000.0
+}
+
+fn days(seconds: f64) -> String {
+    format!("{:.1} d", seconds / 86400.0)
+}
+
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Abstraction {
+    // prefix, set, multiset or last:K
+    pub fn parse(spec: &str) -> Result<Abstraction, Box<dyn Error>> {
+        match spec.split_once(':') {
+            None if spec == "prefix" => Ok(Abstraction::Prefix),
+            None if spec == "set" => Ok(Abstraction::Set),
+            None if spec == "multiset" => Ok(Abstraction::Multiset),
+            Some(("last", k)) => match k.parse::<usize>() {
+                Ok(k) if k > 0 => Ok(Abstraction::LastK(k)),
+                _ => Err(format!("Invalid number of last activities: {}", spec).into()),
+            },
+            _ => Err(format!("Invalid abstraction (prefix, set, multiset or last:K): {}", spec).into()),
+        }
+    }
+
+    // The state of a case with these activities so far
+    pub fn state(&self, prefix: &[u8]) -> Vec<u8> {
+        match self {
+            Abstraction::Prefix => prefix.to_vec(),
+            Abstraction::LastK(k) => prefix[prefix.len().saturating_sub(*k)..].to_vec(),
+            Abstraction::Set | Abstraction::Multiset => {
+                let mut state = prefix.to_vec();
+                state.sort_unstable();
+                if *self == Abstraction::Set {
+                    state.dedup();
+                }
+                state
+            }
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Abstraction::Prefix => "prefix".to_string(),
+            Abstraction::LastK(k) => format!("last:{}", k),
+            Abstraction::Set => "set".to_string(),
+            Abstraction::Multiset => "multiset".to_string(),
+        }
+    }
+
+    // Readable state, e.g. "A, B" (a sequence) or "{A, B}"
+    fn label(&self, state: &[u8]) -> String {
+        if state.is_empty() {
+            return "start".to_string();
+        }
+        let names = state.iter().map(|&num| activities::num_to_str(num)).collect::<Vec<_>>().join(", ");
+        match self {
+            Abstraction::Set | Abstraction::Multiset => format!("{{{}}}", names),
+            _ => names,
+        }
+    }
+}
+
+impl StateStats {
+    fn add(&mut self, elapsed: f64, remaining: f64, next: Option<u8>) {
+        self.visits += 1;
+        self.elapsed_sum += elapsed;
+        self.remaining_sum += remaining;
+        *self.next.entry(next).or_insert(0) += 1;
+    }
+
+    pub fn mean_elapsed(&self) -> f64 {
+        self.elapsed_sum / self.visits as f64
+    }
+
+    pub fn mean_remaining(&self) -> f64 {
+        self.remaining_sum / self.visits as f64
+    }
+
+    // Most frequent next activity and its probability
+    pub fn next_activity(&self) -> (Option<u8>, f64) {
+        let (next, count) = self.next
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(next, count)| (*next, *count))
+            .unwrap_or((None, 0));
+        (next, count as f64 / self.visits as f64)
+    }
+}
+
+impl TransitionStats {
+    pub fn mean_seconds(&self) -> f64 {
+        self.seconds_sum / self.visits as f64
+    }
+}
+
+impl TransitionSystem {
+    pub fn build(traces: &[Trace], abstraction: Abstraction) -> TransitionSystem {
+        let mut system = TransitionSystem { abstraction, states: BTreeMap::new(), transitions: BTreeMap::new() };
+        for trace in traces.iter().filter(|trace| !trace.activities.is_empty()) {
+            let (start, end) = (trace.timestamps[0], trace.timestamps[trace.timestamps.len() - 1]);
+            let mut from = abstraction.state(&[]);
+            system.states.entry(from.clone()).or_default().add(0.0, seconds(start, end), Some(trace.activities[0]));
+            for (i, (&activity, &timestamp)) in trace.activities.iter().zip(&trace.timestamps).enumerate() {
+                let to = abstraction.state(&trace.activities[..=i]);
+                let next = trace.activities.get(i + 1).copied();
+                system.states.entry(to.clone()).or_default().add(seconds(start, timestamp), seconds(timestamp, end), next);
+
+                let previous = if i == 0 { start } else { trace.timestamps[i - 1] };
+                let transition = system.transitions.entry((from, activity, to.clone())).or_default();
+                transition.visits += 1;
+                transition.seconds_sum += seconds(previous, timestamp);
+                from = to;
+            }
+        }
+        system
+    }
+
+    // The state of a case with these activities so far (None if no case got there)
+    pub fn state_of(&self, prefix: &[u8]) -> Option<&StateStats> {
+        self.states.get(&self.abstraction.state(prefix))
+    }
+
+    pub fn to_json(&self) -> Value {
+        let ids: HashMap<&Vec<u8>, usize> = self.states.keys().enumerate().map(|(id, state)| (state, id)).collect();
+        json!({
+            "abstraction": self.abstraction.name(),
+            "states": self.states
+                .iter()
+                .map(|(state, stats)| json!({
+                    "id": ids[state],
+                    "state": state.iter().map(|&num| activities::num_to_str(num)).collect::<Vec<_>>(),
+                    "visits": stats.visits,
+                    "mean_elapsed_seconds": stats.mean_elapsed(),
+                    "mean_remaining_seconds": stats.mean_remaining(),
+                    "next": stats.next
+                        .iter()
+                        .map(|(next, count)| (next.map_or("(end)", activities::num_to_str), *count))
+                        .collect::<BTreeMap<_, _>>(),
+                }))
+                .collect::<Vec<_>>(),
+            "transitions": self.transitions
+                .iter()
+                .map(|((from, activity, to), stats)| json!({
+                    "from": ids[from],
+                    "to": ids[to],
+                    "activity": activities::num_to_str(*activity),
+                    "visits": stats.visits,
+                    "mean_seconds": stats.mean_seconds(),
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    // Graphviz DOT: states with visits and mean elapsed/remaining times, transitions with
+    // visits and mean times (wider for more visits)
+    pub fn to_dot(&self) -> String {
+        let ids: HashMap<&Vec<u8>, usize> = self.states.keys().enumerate().map(|(id, state)| (state, id)).collect();
+        let mut dot = String::from("digraph transition_system {\n    node [shape=box];\n");
+        for (state, stats) in &self.states {
+            dot.push_str(&format!(
+                "    s{} [label=\"{}\\nvisits {}, elapsed {}, remaining {}\"];\n",
+                ids[state],
+                escape(&self.abstraction.label(state)),
+                stats.visits,
+                days(stats.mean_elapsed()),
+                days(stats.mean_remaining())
+            ));
+        }
+        for ((from, activity, to), stats) in &self.transitions {
+            dot.push_str(&format!(
+                "    s{} -> s{} [label=\"{}\\n{} x {}\", penwidth={:.1}];\n",
+                ids[from],
+                ids[to],
+                escape(activities::num_to_str(*activity)),
+                stats.visits,
+                days(stats.mean_seconds()),
+                1.0 + (stats.visits as f64).ln()
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
@@ -0,0 +1,28 @@
+#!/bin/bash
+#
+# Replays a recorded CSV log (case_id;activity;timestamp) in event-time order, as a live feed
+#   ./stream_replay.sh sample_log.csv [delay between events, e.g. 0.01] [host:port]
+# without host:port the events are piped into `procure2pay stream`, otherwise sent to
+# `procure2pay stream --listen host:port` running elsewhere
+set -e
+
+log=${1:?Usage: $0 LOG.csv [DELAY] [HOST:PORT]}
+delay=${2:-0}
+address=$3
+
+replay() {
+    tail -n +2 "$log" | sort -t ';' -k 3 | while IFS= read -r line; do
+        echo "$line"
+        if [ "$delay" != "0" ]; then
+            sleep "$delay"
+        fi
+    done
+}
+
+if [ -z "$address" ]; then
+    replay | cargo run --release -- stream --interval 1 --case-timeout 2592000 --with-names
+else
+    exec 3<>"/dev/tcp/${address%:*}/${address#*:}"
+    replay >&3
+    exec 3>&-
+fi
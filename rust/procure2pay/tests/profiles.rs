@@ -0,0 +1,116 @@
+//
+//  SPDX-License-Identifier: Apache-2.0
+//
+//  profiles: Runs the profiles of procure2pay.toml end to end (the binary on a small log in
+//            place of the input of the profile), and the options which depend on the input format
+//
+use arrow_array::{ArrayRef, RecordBatch, StringArray, TimestampMillisecondArray};
+use std::process::Command;
+use std::sync::Arc;
+
+const EVENTS: [(&str, &str, i64); 5] = [
+    ("1", "Create FI invoice by vendor", 0),
+    ("1", "Clear open item", 86_400_000),
+    ("2", "Create FI invoice by vendor", 3_600_000),
+    ("2", "Clear open item", 90_000_000),
+    ("3", "Create FI invoice by vendor", 7_200_000),
+];
+
+fn run(args: &[&str]) -> serde_json::Value {
+    let output = Command::new(env!("CARGO_BIN_EXE_procure2pay"))
+        .args(args)
+        .arg("--no-time-eval")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+// The error message of a run which must fail
+fn run_error(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_procure2pay")).args(args).output().unwrap();
+    assert!(!output.status.success());
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+fn run_profile(profile: &str, log_path: &str) -> serde_json::Value {
+    run(&["--config", "procure2pay.toml", "--profile", profile, log_path])
+}
+
+fn write_parquet(path: &std::path::Path, columns: Vec<(&str, ArrayRef)>) {
+    let batch = RecordBatch::try_from_iter(columns).unwrap();
+    let mut writer = parquet::arrow::ArrowWriter::try_new(std::fs::File::create(path).unwrap(), batch.schema(), None).unwrap();
+    writer.write(&batch).unwrap();
+    writer.close().unwrap();
+}
+
+#[test]
+fn test_csv_profile() {
+    let log_path = std::env::temp_dir().join(format!("procure2pay_profile_{}.csv", std::process::id()));
+    let lines: Vec<String> = EVENTS
+        .iter()
+        .map(|(case_id, activity, millis)| {
+            let timestamp = chrono::DateTime::from_timestamp_millis(*millis).unwrap().naive_utc().format("%Y-%m-%d %H:%M:%S%.3f");
+            format!("{};{};{}", case_id, activity, timestamp)
+        })
+        .collect();
+    std::fs::write(&log_path, format!("case_id;activity;timestamp\n{}\n", lines.join("\n"))).unwrap();
+
+    let variants = run_profile("p2p-sap", log_path.to_str().unwrap());
+    assert_eq!(variants, serde_json::json!([[["Create FI invoice by vendor", "Clear open item"], 2], [["Create FI invoice by vendor"], 1]]));
+    let _ = std::fs::remove_file(&log_path);
+}
+
+#[test]
+fn test_parquet_profile() {
+    // The column names of the profile (case, event, time)
+    let columns: Vec<(&str, ArrayRef)> = vec![
+        ("case", Arc::new(StringArray::from_iter_values(EVENTS.iter().map(|event| event.0)))),
+        ("event", Arc::new(StringArray::from_iter_values(EVENTS.iter().map(|event| event.1)))),
+        ("time", Arc::new(TimestampMillisecondArray::from_iter_values(EVENTS.iter().map(|event| event.2)))),
+    ];
+    let log_path = std::env::temp_dir().join(format!("procure2pay_profile_{}.parquet", std::process::id()));
+    write_parquet(&log_path, columns);
+
+    let log_path = log_path.to_str().unwrap();
+    let variants = run_profile("p2p-parquet", log_path);
+    assert_eq!(variants, serde_json::json!([[["Create FI invoice by vendor", "Clear open item"], 2], [["Create FI invoice by vendor"], 1]]));
+    // The same engine without the renames of the profile (which need the events anyway)
+    let columns = ["--case-column=case", "--activity-column=event", "--timestamp-column=time"];
+    let expected = run(&[&[log_path, "--parquet", "--gold"][..], &columns].concat());
+    assert_eq!(expected, serde_json::json!([[[9, 4], 2], [[9], 1]]));
+    assert_eq!(run(&[&[log_path, "--parquet", "--interned"][..], &columns].concat()), expected);
+    let _ = std::fs::remove_file(log_path);
+}
+
+#[test]
+fn test_input_format_options() {
+    // The resources are extra columns of the CSV, SQLite and Parquet input has none
+    for input in ["--sqlite", "--parquet"] {
+        assert!(run_error(&["log", input, "--resources"]).contains("cannot be used with"));
+        // Without a case table every case would be "(missing)"
+        assert!(run_error(&["log", input, "--group-by", "vendor"]).contains("requires --case-table"));
+    }
+
+    // The case attributes of Parquet input from the case table
+    let columns: Vec<(&str, ArrayRef)> = vec![
+        ("case_id", Arc::new(StringArray::from_iter_values(EVENTS.iter().map(|event| event.0)))),
+        ("activity", Arc::new(StringArray::from_iter_values(EVENTS.iter().map(|event| event.1)))),
+        ("timestamp", Arc::new(TimestampMillisecondArray::from_iter_values(EVENTS.iter().map(|event| event.2)))),
+    ];
+    let log_path = std::env::temp_dir().join(format!("procure2pay_group_by_{}.parquet", std::process::id()));
+    write_parquet(&log_path, columns);
+    let table_path = std::env::temp_dir().join(format!("procure2pay_group_by_{}.csv", std::process::id()));
+    std::fs::write(&table_path, "case_id;vendor\n1;A\n2;A\n3;B\n").unwrap();
+
+    let breakdown = run(&[log_path.to_str().unwrap(), "--parquet", "--group-by", "vendor", "--case-table", table_path.to_str().unwrap()]);
+    let groups: Vec<(&str, u64)> = breakdown["groups"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|group| (group["value"].as_str().unwrap(), group["cases"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(groups, vec![("A", 2), ("B", 1)]);
+    let _ = std::fs::remove_file(&log_path);
+    let _ = std::fs::remove_file(&table_path);
+}